@@ -0,0 +1,191 @@
+//! Times the collision checks of courses crowded with colliders, with the
+//! broadphase and with every pair checked, then prints both.
+//!
+//! Usage: `cargo bench --bench collisions`
+//!
+//! Each course is a row of pipes, score gaps and coins spread along x, flappy
+//! flying through it one physics step at a time. Both find the same contacts,
+//! or the benchmark fails.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use bevy_flappy::collider::{Collider, CollisionLayers};
+use bevy_flappy::collision::{self, Body};
+use bevy_flappy::config::GameConfig;
+use bevy_flappy::flappy::{self, FLAPPY_SPRITE_SCALE};
+use bevy_flappy::physics::PHYSICS_DELTA_SECONDS;
+use bevy_flappy::pipe::PIPE_WIDTH;
+use bevy_flappy::seed::CourseRng;
+
+const BENCHMARK_COLLIDER_COUNTS: [usize; 3] = [100, 1000, 5000];
+const BENCHMARK_STEPS: u32 = 300;
+const BENCHMARK_SEED: u64 = 0;
+// Distance along the course between two colliders, a few per screen
+const BENCHMARK_COLLIDER_SPACING: f32 = 60.0;
+const BENCHMARK_COIN_RADIUS: f32 = 16.0;
+
+type PairsFinder = fn(&[Body]) -> Vec<(usize, usize)>;
+
+fn main() {
+    let config = GameConfig::default();
+
+    for collider_count in BENCHMARK_COLLIDER_COUNTS {
+        let still_colliders =
+            crowded_course(&config, collider_count, &mut CourseRng::new(BENCHMARK_SEED));
+        let flappy_collider = flappy::collider();
+
+        let mut brute_force_duration = Duration::ZERO;
+        let mut broadphase_duration = Duration::ZERO;
+        let mut contact_count = 0;
+        for step in 0..BENCHMARK_STEPS {
+            let bodies: Vec<Body> =
+                std::iter::once(flappy_body(&flappy_collider, &config, step))
+                    .chain(still_colliders.iter().enumerate().map(
+                        |(index, (collider, transform))| {
+                            Body::still(
+                                Entity::from_raw(index as u32 + 1),
+                                collider,
+                                collider.placed(transform),
+                            )
+                        },
+                    ))
+                    .collect();
+
+            // in turn first, neither one always finds the bodies already in
+            // the cache
+            let (brute_force_contacts, broadphase_contacts) = if step % 2 == 0 {
+                let brute_force_contacts = timed_contacts(
+                    &bodies,
+                    collision::brute_force_pairs,
+                    &mut brute_force_duration,
+                );
+                let broadphase_contacts = timed_contacts(
+                    &bodies,
+                    collision::broadphase_pairs,
+                    &mut broadphase_duration,
+                );
+                (brute_force_contacts, broadphase_contacts)
+            } else {
+                let broadphase_contacts = timed_contacts(
+                    &bodies,
+                    collision::broadphase_pairs,
+                    &mut broadphase_duration,
+                );
+                let brute_force_contacts = timed_contacts(
+                    &bodies,
+                    collision::brute_force_pairs,
+                    &mut brute_force_duration,
+                );
+                (brute_force_contacts, broadphase_contacts)
+            };
+
+            assert_eq!(
+                broadphase_contacts, brute_force_contacts,
+                "the broadphase missed contacts at step {}",
+                step
+            );
+            contact_count += broadphase_contacts.len();
+        }
+
+        let per_step = |duration: Duration| duration.as_secs_f64() * 1e6 / BENCHMARK_STEPS as f64;
+        println!(
+            "colliders: {}, steps: {}, contacts: {}, brute force: {:.1}µs per step, broadphase: {:.1}µs per step, {:.1}x faster",
+            collider_count,
+            BENCHMARK_STEPS,
+            contact_count,
+            per_step(brute_force_duration),
+            per_step(broadphase_duration),
+            brute_force_duration.as_secs_f64() / broadphase_duration.as_secs_f64().max(f64::EPSILON)
+        );
+    }
+}
+
+/// The pairs of `bodies` touching along the step, found among the pairs of
+/// `find_pairs`, adding the time it took to `duration`
+fn timed_contacts(
+    bodies: &[Body],
+    find_pairs: PairsFinder,
+    duration: &mut Duration,
+) -> Vec<(usize, usize)> {
+    let start = Instant::now();
+    let contacts = collision::contacts(bodies, find_pairs(black_box(bodies)))
+        .map(|(index, other_index, _)| (index, other_index))
+        .collect();
+    *duration += start.elapsed();
+
+    black_box(contacts)
+}
+
+/// Pipes, score gaps and coins in turn, `BENCHMARK_COLLIDER_SPACING` apart
+/// from the start of the course, at random heights in the window
+fn crowded_course(
+    config: &GameConfig,
+    collider_count: usize,
+    course_rng: &mut CourseRng,
+) -> Vec<(Collider, GlobalTransform)> {
+    let half_height = config.window_height / 2.0;
+
+    (0..collider_count)
+        .map(|index| {
+            let x = index as f32 * BENCHMARK_COLLIDER_SPACING;
+            let y = course_rng.rng().gen_range(-half_height..=half_height);
+
+            match index % 3 {
+                0 => (
+                    Collider::new(Vec2::ONE),
+                    Transform {
+                        translation: Vec3::new(x, y, 1.0),
+                        scale: Vec3::new(PIPE_WIDTH, half_height, 0.0),
+                        ..default()
+                    },
+                ),
+                1 => (
+                    Collider::new(Vec2::ONE)
+                        .with_layers(CollisionLayers::SCORE_TRIGGER)
+                        .into_sensor(),
+                    Transform {
+                        translation: Vec3::new(x, y, 1.0),
+                        scale: Vec3::new(PIPE_WIDTH / 2.0, half_height / 2.0, 0.0),
+                        ..default()
+                    },
+                ),
+                _ => (
+                    Collider::circle(BENCHMARK_COIN_RADIUS)
+                        .with_layers(CollisionLayers::PICKUP)
+                        .into_sensor(),
+                    Transform::from_xyz(x, y, 1.0),
+                ),
+            }
+        })
+        .map(|(collider, transform)| (collider, transform.into()))
+        .collect()
+}
+
+/// Flappy flying along the course at the scrolling speed, bobbing up and
+/// down, over the physics step `step`
+fn flappy_body(collider: &Collider, config: &GameConfig, step: u32) -> Body {
+    let position = |step: u32| {
+        let time = step as f32 * PHYSICS_DELTA_SECONDS;
+        Vec3::new(
+            time * config.scrolling_speed,
+            (time * 2.0).sin() * config.window_height / 4.0,
+            1.0,
+        )
+    };
+    let start = Transform {
+        translation: position(step),
+        scale: FLAPPY_SPRITE_SCALE,
+        ..default()
+    };
+
+    Body::moving(
+        Entity::from_raw(0),
+        collider,
+        collider.placed(&start.into()),
+        (position(step + 1) - start.translation).truncate(),
+    )
+}
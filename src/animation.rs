@@ -1,44 +1,603 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::ecs::query::{ROQueryItem, WorldQuery};
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use serde::Deserialize;
+
+use crate::game_state::unless_paused;
+
+/// Sprite sheet and its named animation clips, read from a `.anim.ron` asset,
+/// for example:
+///
+/// ```ron
+/// (
+///     texture: "characters.png",
+///     tile_size: (24.0, 24.0),
+///     columns: 9,
+///     rows: 3,
+///     clips: {
+///         "flap": (
+///             mode: Once,
+///             frames: [
+///                 (index: 25, duration: 0.1),
+///                 (index: 26, duration: 0.1, events: ["wing_down"]),
+///             ],
+///         ),
+///     },
+/// )
+/// ```
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "2b0d6c51-7a3e-4f0b-b8c4-5e9f1d2a6c37"]
+#[serde(deny_unknown_fields)]
+pub struct AnimationLibrary {
+    /// Sprite sheet, relative to the assets folder
+    pub texture: String,
+    pub tile_size: Vec2,
+    pub columns: usize,
+    pub rows: usize,
+    pub clips: HashMap<String, AnimationClip>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnimationClip {
+    pub mode: AnimationMode,
+    pub frames: Vec<AnimationFrame>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum AnimationMode {
+    /// Back to the first frame after the last one
+    Loop,
+    /// Stops on the last frame, then sends an `AnimationFinished`
+    Once,
+    /// Back and forth between the first and the last frame
+    PingPong,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnimationFrame {
+    /// Tile of the sprite sheet, counted row by row from the top left
+    pub index: usize,
+    /// In seconds
+    pub duration: f32,
+    /// Names of the `AnimationFrameEvent`s sent when the frame is shown
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct InvalidAnimationLibrary(Vec<String>);
+
+impl fmt::Display for InvalidAnimationLibrary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid animation library:")?;
+        for problem in &self.0 {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for InvalidAnimationLibrary {}
 
-#[derive(Component)]
+impl AnimationLibrary {
+    fn validate(&self) -> Result<(), InvalidAnimationLibrary> {
+        let mut problems = Vec::new();
+        let tile_count = self.columns * self.rows;
+
+        for (name, clip) in &self.clips {
+            if clip.frames.is_empty() {
+                problems.push(format!("clip {} has no frames", name));
+            }
+            for frame in &clip.frames {
+                if frame.index >= tile_count {
+                    problems.push(format!(
+                        "clip {} shows tile {}, the sheet has {}",
+                        name, frame.index, tile_count
+                    ));
+                }
+                if !(frame.duration.is_finite() && frame.duration > 0.0) {
+                    problems.push(format!(
+                        "clip {} has a frame lasting {} seconds, it must be greater than 0",
+                        name, frame.duration
+                    ));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(InvalidAnimationLibrary(problems))
+        }
+    }
+}
+
+/// Plays a clip of an `AnimationLibrary` on the `TextureAtlasSprite` of the
+/// entity. Switch clips with `play`, or send an `AnimationCommand` from systems
+/// that do not query it.
+#[derive(Component, Debug)]
 pub struct Animation {
-    pub current_frame: usize,
-    pub timer: Timer,
-    pub frames: Vec<usize>,
+    library: Handle<AnimationLibrary>,
+    clip: String,
+    frame: usize,
+    /// Time spent on the current frame
+    frame_elapsed: f32,
+    /// Going from the last frame to the first one, in `AnimationMode::PingPong`
+    backward: bool,
+    finished: bool,
+    /// The events of the first frame are sent on the next update
+    started: bool,
+    /// Playback speed multiplier, frames last their `duration` at 1
+    speed: f32,
+    paused: bool,
+}
+
+impl Animation {
+    pub fn new(library: Handle<AnimationLibrary>, clip: &str) -> Self {
+        Animation {
+            library,
+            clip: clip.to_string(),
+            frame: 0,
+            frame_elapsed: 0.0,
+            backward: false,
+            finished: false,
+            started: true,
+            speed: 1.0,
+            paused: false,
+        }
+    }
+
+    /// Switch to `clip`, from its first frame even when already playing it
+    pub fn play(&mut self, clip: &str) {
+        if self.clip != clip {
+            self.clip = clip.to_string();
+        }
+        self.replay();
+    }
+
+    /// Play the current clip again from its first frame
+    pub fn replay(&mut self) {
+        self.frame = 0;
+        self.frame_elapsed = 0.0;
+        self.backward = false;
+        self.finished = false;
+        self.started = true;
+    }
+
+    /// Show `frame` of the current clip from its start, sending its events.
+    /// A frame the clip does not have restarts it.
+    pub fn seek(&mut self, frame: usize) {
+        self.frame = frame;
+        self.frame_elapsed = 0.0;
+        self.finished = false;
+        self.started = true;
+    }
+
+    /// Frozen on the current frame while paused, keeping the time spent on it
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Play faster above 1 and slower below, negative speeds stop it
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.0);
+    }
+
+    /// Move forward in `clip` by `delta_seconds`, calling `enter_frame` with
+    /// every frame shown on the way. Returns whether the clip just finished.
+    fn advance(
+        &mut self,
+        clip: &AnimationClip,
+        delta_seconds: f32,
+        mut enter_frame: impl FnMut(&AnimationFrame),
+    ) -> bool {
+        // the clip may have lost frames on a reload
+        if self.frame >= clip.frames.len() {
+            self.replay();
+        }
+        if std::mem::take(&mut self.started) {
+            enter_frame(&clip.frames[self.frame]);
+        }
+        if self.finished {
+            return false;
+        }
+
+        self.frame_elapsed += delta_seconds;
+        let last_frame = clip.frames.len() - 1;
+
+        while self.frame_elapsed >= clip.frames[self.frame].duration {
+            self.frame_elapsed -= clip.frames[self.frame].duration;
+
+            match clip.mode {
+                AnimationMode::Loop => {
+                    self.frame = if self.frame == last_frame {
+                        0
+                    } else {
+                        self.frame + 1
+                    };
+                }
+                AnimationMode::Once => {
+                    if self.frame == last_frame {
+                        self.frame_elapsed = 0.0;
+                        self.finished = true;
+                        return true;
+                    }
+                    self.frame += 1;
+                }
+                AnimationMode::PingPong => {
+                    if last_frame == 0 {
+                        continue;
+                    }
+                    if self.frame == last_frame {
+                        self.backward = true;
+                    } else if self.frame == 0 {
+                        self.backward = false;
+                    }
+                    self.frame = if self.backward {
+                        self.frame - 1
+                    } else {
+                        self.frame + 1
+                    };
+                }
+            }
+            enter_frame(&clip.frames[self.frame]);
+        }
+
+        false
+    }
 }
 
 pub struct AnimationPlugin;
 
 impl Plugin for AnimationPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<AnimationReplayEvent>();
-        app.add_system(play_animation);
-        app.add_system(replay_animation);
+        app.add_asset::<AnimationLibrary>();
+        app.init_asset_loader::<AnimationLibraryLoader>();
+        app.add_event::<AnimationCommand>();
+        app.add_event::<AnimationFinished>();
+        app.add_event::<AnimationFrameEvent>();
+        app.add_system(apply_animation_commands.before(play_animation));
+        app.add_system(play_animation.with_run_criteria(unless_paused));
+    }
+}
+
+/// Texture atlases of the animation libraries, attached to every animated
+/// sprite. Only added when rendering.
+pub struct AnimationAtlasPlugin;
+
+impl Plugin for AnimationAtlasPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AnimationAtlases>();
+        app.add_system(build_texture_atlases);
+        app.add_system(attach_texture_atlases.after(build_texture_atlases));
+    }
+}
+
+#[derive(Default)]
+struct AnimationLibraryLoader;
+
+impl AssetLoader for AnimationLibraryLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let library: AnimationLibrary = ron::de::from_bytes(bytes)?;
+            library.validate()?;
+            load_context.set_default_asset(LoadedAsset::new(library));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["anim.ron"]
+    }
+}
+
+/// Change how the `Animation` of an entity plays, applied before the next
+/// update. Commands to entities without one are dropped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnimationCommand {
+    /// Play the current clip again from its first frame
+    Replay(Entity),
+    SetPaused(Entity, bool),
+    /// Jump to a frame of the current clip
+    Seek(Entity, usize),
+    /// See `Animation::set_speed`
+    SetSpeed(Entity, f32),
+}
+
+impl AnimationCommand {
+    pub fn entity(&self) -> Entity {
+        match *self {
+            AnimationCommand::Replay(entity)
+            | AnimationCommand::SetPaused(entity, _)
+            | AnimationCommand::Seek(entity, _)
+            | AnimationCommand::SetSpeed(entity, _) => entity,
+        }
+    }
+}
+
+/// Sent when an `AnimationMode::Once` clip reaches the end of its last frame
+pub struct AnimationFinished {
+    pub entity: Entity,
+    pub clip: String,
+}
+
+/// Sent when an animation shows a frame with `events`, read them through
+/// `AnimationEvents`
+pub struct AnimationFrameEvent {
+    pub entity: Entity,
+    pub name: String,
+}
+
+/// Subscription to the `AnimationFrameEvent`s of a system
+#[derive(SystemParam)]
+pub struct AnimationEvents<'w, 's> {
+    events: EventReader<'w, 's, AnimationFrameEvent>,
+}
+
+impl<'w, 's> AnimationEvents<'w, 's> {
+    /// Entities that showed a frame with the `name` event since the last read,
+    /// once per frame shown. Events with other names are used up all the same,
+    /// a later read of this subscription won't see them.
+    pub fn read<'a>(&'a mut self, name: &'a str) -> impl Iterator<Item = Entity> + 'a {
+        self.events
+            .iter()
+            .filter(move |event| event.name == name)
+            .map(|event| event.entity)
+    }
+
+    /// Like `read`, but only for the entities of `query`, given as their
+    /// items. Events of other entities are used up too.
+    pub fn read_from<'a, Q: WorldQuery, F: WorldQuery>(
+        &'a mut self,
+        query: &'a Query<'_, '_, Q, F>,
+        name: &'a str,
+    ) -> impl Iterator<Item = ROQueryItem<'a, Q>> + 'a {
+        self.read(name).filter_map(|entity| query.get(entity).ok())
     }
 }
 
-pub struct AnimationReplayEvent(pub Entity);
+#[derive(Default)]
+struct AnimationAtlases(HashMap<Handle<AnimationLibrary>, Handle<TextureAtlas>>);
 
-fn play_animation(time: Res<Time>, mut query: Query<(&mut Animation, &mut TextureAtlasSprite)>) {
-    for (mut animation, mut sprite) in &mut query {
-        animation.timer.tick(time.delta());
-        animation.current_frame =
-            ((animation.timer.percent() - 0.01) * (animation.frames.len() as f32)).floor() as usize;
-        sprite.index = animation.frames[animation.current_frame];
+//
+// -- SYSTEM
+//
+
+fn play_animation(
+    time: Res<Time>,
+    libraries: Res<Assets<AnimationLibrary>>,
+    mut finished_events: EventWriter<AnimationFinished>,
+    mut frame_events: EventWriter<AnimationFrameEvent>,
+    mut query: Query<(Entity, &mut Animation, &mut TextureAtlasSprite)>,
+) {
+    for (entity, mut animation, mut sprite) in &mut query {
+        let Some(clip) = libraries
+            .get(&animation.library)
+            .and_then(|library| library.clips.get(&animation.clip))
+        else {
+            continue;
+        };
+
+        let delta_seconds = if animation.paused {
+            0.0
+        } else {
+            time.delta_seconds() * animation.speed
+        };
+        let is_finished = animation.advance(clip, delta_seconds, |frame| {
+            for name in &frame.events {
+                frame_events.send(AnimationFrameEvent {
+                    entity,
+                    name: name.clone(),
+                });
+            }
+        });
+        if is_finished {
+            finished_events.send(AnimationFinished {
+                entity,
+                clip: animation.clip.clone(),
+            });
+        }
+        sprite.index = clip.frames[animation.frame].index;
+    }
+}
+
+fn apply_animation_commands(
+    mut commands: EventReader<AnimationCommand>,
+    mut query: Query<&mut Animation>,
+) {
+    for command in commands.iter() {
+        let Ok(mut animation) = query.get_mut(command.entity()) else {
+            continue;
+        };
+
+        match *command {
+            AnimationCommand::Replay(_) => animation.replay(),
+            AnimationCommand::SetPaused(_, paused) => animation.set_paused(paused),
+            AnimationCommand::Seek(_, frame) => animation.seek(frame),
+            AnimationCommand::SetSpeed(_, speed) => animation.set_speed(speed),
+        }
     }
 }
 
-fn replay_animation(
-    mut reset_events: EventReader<AnimationReplayEvent>,
-    mut query: Query<(Entity, &mut Animation)>,
+/// Build the atlas of a library once loaded, and again when its file changes
+fn build_texture_atlases(
+    mut library_events: EventReader<AssetEvent<AnimationLibrary>>,
+    libraries: Res<Assets<AnimationLibrary>>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut animation_atlases: ResMut<AnimationAtlases>,
+    mut query: Query<(&Animation, &mut Handle<TextureAtlas>)>,
 ) {
-    for reset_event in reset_events.iter() {
-        for (entity, mut animation) in query.iter_mut() {
-            if entity == reset_event.0 {
-                animation.current_frame = 0;
-                animation.timer.reset();
-                animation.timer.unpause();
+    for event in library_events.iter() {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                let Some(library) = libraries.get(handle) else {
+                    continue;
+                };
+
+                let texture_atlas = TextureAtlas::from_grid(
+                    asset_server.load(library.texture.as_str()),
+                    library.tile_size,
+                    library.columns,
+                    library.rows,
+                );
+                let texture_atlas_handle = texture_atlases.add(texture_atlas);
+
+                for (animation, mut sprite_texture_atlas) in &mut query {
+                    if animation.library == *handle {
+                        *sprite_texture_atlas = texture_atlas_handle.clone();
+                    }
+                }
+                animation_atlases
+                    .0
+                    .insert(handle.clone_weak(), texture_atlas_handle);
+            }
+            AssetEvent::Removed { handle } => {
+                animation_atlases.0.remove(handle);
             }
         }
     }
 }
+
+/// Give the atlas of their library to the sprites spawned once it is built
+fn attach_texture_atlases(
+    animation_atlases: Res<AnimationAtlases>,
+    mut query: Query<(&Animation, &mut Handle<TextureAtlas>), Added<Animation>>,
+) {
+    for (animation, mut sprite_texture_atlas) in &mut query {
+        if let Some(texture_atlas_handle) = animation_atlases.0.get(&animation.library) {
+            *sprite_texture_atlas = texture_atlas_handle.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // exact in binary, so frames end right on time
+    const FRAME_DURATION: f32 = 0.25;
+
+    fn clip(mode: AnimationMode, frame_count: usize) -> AnimationClip {
+        AnimationClip {
+            mode,
+            frames: (0..frame_count)
+                .map(|index| AnimationFrame {
+                    index,
+                    duration: FRAME_DURATION,
+                    events: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The tiles shown by `advance`, and whether it finished the clip
+    fn advance(animation: &mut Animation, clip: &AnimationClip, frames: f32) -> (Vec<usize>, bool) {
+        let mut shown = Vec::new();
+        let is_finished = animation.advance(clip, frames * FRAME_DURATION, |frame| {
+            shown.push(frame.index)
+        });
+
+        (shown, is_finished)
+    }
+
+    #[test]
+    fn loop_starts_over_after_the_last_frame() {
+        let clip = clip(AnimationMode::Loop, 3);
+        let mut animation = Animation::new(Handle::default(), "loop");
+
+        assert_eq!(advance(&mut animation, &clip, 0.5), (vec![0], false));
+        assert_eq!(advance(&mut animation, &clip, 0.5), (vec![1], false));
+        assert_eq!(advance(&mut animation, &clip, 2.5), (vec![2, 0], false));
+        assert_eq!(animation.frame, 0);
+        assert_eq!(animation.frame_elapsed, 0.5 * FRAME_DURATION);
+    }
+
+    #[test]
+    fn once_stops_on_the_last_frame() {
+        let clip = clip(AnimationMode::Once, 2);
+        let mut animation = Animation::new(Handle::default(), "once");
+
+        assert_eq!(advance(&mut animation, &clip, 1.0), (vec![0, 1], false));
+        assert_eq!(advance(&mut animation, &clip, 1.0), (vec![], true));
+        assert_eq!(advance(&mut animation, &clip, 1.0), (vec![], false));
+        assert_eq!(animation.frame, 1);
+
+        animation.replay();
+        assert_eq!(advance(&mut animation, &clip, 10.0), (vec![0, 1], true));
+    }
+
+    #[test]
+    fn ping_pong_turns_around_at_both_ends() {
+        let clip = clip(AnimationMode::PingPong, 3);
+        let mut animation = Animation::new(Handle::default(), "ping_pong");
+
+        let shown: Vec<usize> = (0..7)
+            .flat_map(|_| advance(&mut animation, &clip, 1.0).0)
+            .collect();
+        assert_eq!(shown, vec![0, 1, 2, 1, 0, 1, 2, 1]);
+    }
+
+    #[test]
+    fn large_steps_show_every_frame_on_the_way() {
+        let clip = clip(AnimationMode::PingPong, 3);
+        let mut animation = Animation::new(Handle::default(), "ping_pong");
+
+        assert_eq!(
+            advance(&mut animation, &clip, 6.5),
+            (vec![0, 1, 2, 1, 0, 1, 2], false)
+        );
+        assert_eq!(animation.frame, 2);
+        assert_eq!(animation.frame_elapsed, 0.5 * FRAME_DURATION);
+    }
+
+    #[test]
+    fn single_frame_clips() {
+        let loop_clip = clip(AnimationMode::Loop, 1);
+        let mut animation = Animation::new(Handle::default(), "loop");
+        assert_eq!(
+            advance(&mut animation, &loop_clip, 2.0),
+            (vec![0, 0, 0], false)
+        );
+
+        let ping_pong_clip = clip(AnimationMode::PingPong, 1);
+        let mut animation = Animation::new(Handle::default(), "ping_pong");
+        assert_eq!(
+            advance(&mut animation, &ping_pong_clip, 2.0),
+            (vec![0], false)
+        );
+        assert_eq!(animation.frame, 0);
+
+        let once_clip = clip(AnimationMode::Once, 1);
+        let mut animation = Animation::new(Handle::default(), "once");
+        assert_eq!(advance(&mut animation, &once_clip, 2.0), (vec![0], true));
+    }
+
+    #[test]
+    fn clip_shrunk_by_a_reload_starts_over() {
+        let mut animation = Animation::new(Handle::default(), "loop");
+        advance(&mut animation, &clip(AnimationMode::Loop, 3), 2.5);
+        assert_eq!(animation.frame, 2);
+
+        assert_eq!(
+            advance(&mut animation, &clip(AnimationMode::Loop, 2), 0.5),
+            (vec![0], false)
+        );
+        assert_eq!(animation.frame, 0);
+        assert_eq!(animation.frame_elapsed, 0.5 * FRAME_DURATION);
+    }
+}
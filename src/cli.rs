@@ -0,0 +1,10 @@
+/// Value following `name` on the command line, e.g. `--seed 42`
+pub fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args();
+    args.find(|arg| arg == name)?;
+    args.next()
+}
+
+pub fn has_flag(name: &str) -> bool {
+    std::env::args().any(|arg| arg == name)
+}
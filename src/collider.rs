@@ -1,26 +1,952 @@
+use std::ops::BitOr;
+
 use bevy::prelude::*;
+use bevy::sprite::Rect;
 
+/// Two colliders collide when each one is on a layer of the other's mask.
+///
+/// Solid colliders stop the moving ones where they touch them, sensors are
+/// gone through and only send the collision events.
 #[derive(Component)]
 pub struct Collider {
-    /// Collider box position relative to the entity's transform
-    position: Vec3,
-    /// Collider box scale
-    scale: Vec2,
+    /// Position of the shape center, in the units of the entity
+    offset: Vec2,
+    shape: ColliderShape,
+    layers: CollisionLayers,
+    mask: CollisionLayers,
+    sensor: bool,
+    /// A disabled collider touches nothing
+    pub enabled: bool,
+}
+
+/// Set of layers, what a collider stands for or what it collides with,
+/// combined with `|`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CollisionLayers(u32);
+
+impl CollisionLayers {
+    pub const PLAYER: Self = CollisionLayers(1 << 0);
+    pub const OBSTACLE: Self = CollisionLayers(1 << 1);
+    /// Scores once touched
+    pub const SCORE_TRIGGER: Self = CollisionLayers(1 << 2);
+    /// Collected once touched
+    pub const PICKUP: Self = CollisionLayers(1 << 3);
+    pub const GROUND: Self = CollisionLayers(1 << 4);
+    pub const ALL: Self = CollisionLayers(u32::MAX);
+
+    /// Whether the sets have a layer in common
+    pub fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl BitOr for CollisionLayers {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        CollisionLayers(self.0 | other.0)
+    }
+}
+
+/// Shape of a `Collider`, in the units of its entity, moved, scaled and turned
+/// along with it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColliderShape {
+    Box {
+        size: Vec2,
+    },
+    Circle {
+        radius: f32,
+    },
+    /// Two half circles of `radius`, with their centers `length` apart along
+    /// the x axis, joined by straight sides
+    Capsule {
+        length: f32,
+        radius: f32,
+    },
 }
 
 impl Collider {
-    pub fn new(scale: Vec2) -> Self {
+    /// Box of `size`. Sprites sized by their scale cover a box of `Vec2::ONE`.
+    pub fn new(size: Vec2) -> Self {
+        Self::with_shape(ColliderShape::Box { size })
+    }
+
+    pub fn circle(radius: f32) -> Self {
+        Self::with_shape(ColliderShape::Circle { radius })
+    }
+
+    pub fn capsule(length: f32, radius: f32) -> Self {
+        Self::with_shape(ColliderShape::Capsule { length, radius })
+    }
+
+    fn with_shape(shape: ColliderShape) -> Self {
         Collider {
-            position: Vec3::ZERO,
-            scale,
+            offset: Vec2::ZERO,
+            shape,
+            layers: CollisionLayers::OBSTACLE,
+            mask: CollisionLayers::ALL,
+            sensor: false,
+            enabled: true,
+        }
+    }
+
+    /// Centered on the entity unless moved by `offset`
+    pub fn with_offset(mut self, offset: Vec2) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// A solid obstacle colliding with everything, unless told otherwise
+    pub fn with_layers(mut self, layers: CollisionLayers) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    pub fn with_mask(mut self, mask: CollisionLayers) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    pub fn into_sensor(mut self) -> Self {
+        self.sensor = true;
+        self
+    }
+
+    pub fn layers(&self) -> CollisionLayers {
+        self.layers
+    }
+
+    pub fn mask(&self) -> CollisionLayers {
+        self.mask
+    }
+
+    pub fn is_sensor(&self) -> bool {
+        self.sensor
+    }
+
+    /// The shape in the world, for an entity at `transform`. Round shapes
+    /// stay round, grown by the largest scale of the transform.
+    pub fn placed(&self, transform: &GlobalTransform) -> Shape {
+        let affine = transform.affine();
+        let place = |point: Vec2| affine.transform_point3(point.extend(0.0)).truncate();
+        let radius_scale = affine
+            .matrix3
+            .x_axis
+            .truncate()
+            .length()
+            .max(affine.matrix3.y_axis.truncate().length());
+
+        match self.shape {
+            ColliderShape::Box { size } => Shape::parallelogram(
+                place(self.offset),
+                affine.transform_vector3(Vec3::X * size.x / 2.0).truncate(),
+                affine.transform_vector3(Vec3::Y * size.y / 2.0).truncate(),
+            ),
+            ColliderShape::Circle { radius } => {
+                Shape::circle(place(self.offset), radius * radius_scale)
+            }
+            ColliderShape::Capsule { length, radius } => {
+                let half_segment = Vec2::X * length / 2.0;
+                Shape::capsule(
+                    place(self.offset - half_segment),
+                    place(self.offset + half_segment),
+                    radius * radius_scale,
+                )
+            }
+        }
+    }
+}
+
+/// A collider shape placed in the world: a convex core, a point for a circle,
+/// a segment for a capsule and a polygon for a box, grown by `radius`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shape {
+    points: [Vec2; 4],
+    point_count: usize,
+    radius: f32,
+}
+
+impl Shape {
+    pub fn circle(center: Vec2, radius: f32) -> Self {
+        Shape {
+            points: [center; 4],
+            point_count: 1,
+            radius,
+        }
+    }
+
+    /// Every point closer than `radius` to the segment from `start` to `end`
+    pub fn capsule(start: Vec2, end: Vec2, radius: f32) -> Self {
+        if start == end {
+            return Self::circle(start, radius);
+        }
+
+        Shape {
+            points: [start, end, end, end],
+            point_count: 2,
+            radius,
+        }
+    }
+
+    /// Box spanning `half_x` and `half_y` on each side of `center`, sheared
+    /// when they are not perpendicular
+    pub fn parallelogram(center: Vec2, half_x: Vec2, half_y: Vec2) -> Self {
+        Shape {
+            points: [
+                center - half_x - half_y,
+                center + half_x - half_y,
+                center + half_x + half_y,
+                center - half_x + half_y,
+            ],
+            point_count: 4,
+            radius: 0.0,
+        }
+    }
+
+    /// The same shape moved by `offset`
+    pub fn translated(mut self, offset: Vec2) -> Self {
+        for point in &mut self.points {
+            *point += offset;
+        }
+        self
+    }
+
+    /// Smallest axis aligned box holding the shape
+    pub fn bounds(&self) -> Rect {
+        let (min, max) = self.points().iter().fold(
+            (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+            |(min, max), point| (min.min(*point), max.max(*point)),
+        );
+
+        Rect {
+            min: min - self.radius,
+            max: max + self.radius,
+        }
+    }
+
+    /// Whether the shapes overlap or touch
+    pub fn intersects(&self, other: &Shape) -> bool {
+        gap(self, other) <= 0.0
+    }
+
+    fn points(&self) -> &[Vec2] {
+        &self.points[..self.point_count]
+    }
+
+    /// Sides of the core, a point is a side of no length
+    fn sides(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+        let points = self.points();
+        let side_count = match points.len() {
+            1 | 2 => 1,
+            count => count,
+        };
+
+        (0..side_count).map(move |index| (points[index], points[(index + 1) % points.len()]))
+    }
+
+    /// Directions a separation between two cores shows along, not normalized
+    fn separating_axes(&self) -> impl Iterator<Item = Vec2> + '_ {
+        // a segment is also separated along itself, when in line with the other core
+        let along_segment = (self.point_count == 2).then(|| self.points[1] - self.points[0]);
+
+        self.sides()
+            .filter(|(start, end)| start != end)
+            .map(|(start, end)| (end - start).perp())
+            .chain(along_segment)
+    }
+
+    /// Direction out of the side from `start` to `end` of the core toward
+    /// `point`. Square to the side of a polygon where `point` faces it, which
+    /// still holds when `point` is barely off it.
+    fn normal_at(&self, start: Vec2, end: Vec2, point: Vec2) -> Vec2 {
+        let side_point = closest_point_on_segment(point, start, end);
+        let faces_side = side_point != start && side_point != end;
+
+        if self.point_count > 2 && faces_side {
+            let center = self.points().iter().sum::<Vec2>() / self.point_count as f32;
+            let normal = (end - start).perp().normalize();
+            if normal.dot(start - center) < 0.0 {
+                -normal
+            } else {
+                normal
+            }
+        } else {
+            (point - side_point).normalize_or_zero()
+        }
+    }
+
+    /// Range covered by the core along `axis`
+    fn project(&self, axis: Vec2) -> (f32, f32) {
+        self.points().iter().map(|point| point.dot(axis)).fold(
+            (f32::INFINITY, f32::NEG_INFINITY),
+            |(min, max), projection| (min.min(projection), max.max(projection)),
+        )
+    }
+}
+
+// Steps narrowing down the time of impact of two shapes, each one leaves at
+// most two thirds of the time left to search
+const TIME_OF_IMPACT_ITERATIONS: usize = 40;
+
+/// Where a box or shape moving in a straight line first touches a still one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contact {
+    /// Fraction of the motion done when they touch, 0 when touching from the start
+    pub time: f32,
+    /// Fraction of the motion done when they part, past 1 when still touching
+    /// at its end
+    pub exit_time: f32,
+    /// Where the still one is touched
+    pub point: Vec2,
+    /// Out of the still one where it is touched, toward the moving one
+    pub normal: Vec2,
+}
+
+/// First contact of the `moving` box translated by `motion` with the still
+/// `target`, even when it goes through it
+pub fn sweep(moving: Rect, motion: Vec2, target: Rect) -> Option<Contact> {
+    // a point moving against the target grown by the moving box
+    let center = (moving.min + moving.max) / 2.0;
+    let half_size = (moving.max - moving.min) / 2.0;
+    let grown_min = target.min - half_size;
+    let grown_max = target.max + half_size;
+
+    let mut entry = Vec2::splat(f32::NEG_INFINITY);
+    let mut exit = Vec2::splat(f32::INFINITY);
+    for axis in 0..2 {
+        if motion[axis] == 0.0 {
+            if center[axis] < grown_min[axis] || center[axis] > grown_max[axis] {
+                return None;
+            }
+            continue;
+        }
+
+        let to_min = (grown_min[axis] - center[axis]) / motion[axis];
+        let to_max = (grown_max[axis] - center[axis]) / motion[axis];
+        entry[axis] = to_min.min(to_max);
+        exit[axis] = to_min.max(to_max);
+    }
+
+    let entry_time = entry.max_element();
+    let exit_time = exit.min_element();
+    if entry_time > exit_time || entry_time > 1.0 || exit_time < 0.0 {
+        return None;
+    }
+
+    let time = entry_time.max(0.0);
+    let moved_center = center + motion * time;
+    let normal = if entry_time >= 0.0 {
+        // along the axis entered last
+        if entry.x > entry.y {
+            Vec2::new(-motion.x.signum(), 0.0)
+        } else {
+            Vec2::new(0.0, -motion.y.signum())
+        }
+    } else {
+        // already overlapping, out along the shallowest side
+        let target_center = (target.min + target.max) / 2.0;
+        let penetration =
+            half_size + (target.max - target.min) / 2.0 - (moved_center - target_center).abs();
+        let away = moved_center - target_center;
+        if penetration.x < penetration.y {
+            Vec2::new(if away.x < 0.0 { -1.0 } else { 1.0 }, 0.0)
+        } else {
+            Vec2::new(0.0, if away.y < 0.0 { -1.0 } else { 1.0 })
+        }
+    };
+
+    let touching_min = (moved_center - half_size).max(target.min);
+    let touching_max = (moved_center + half_size).min(target.max);
+
+    Some(Contact {
+        time,
+        exit_time,
+        point: (touching_min + touching_max) / 2.0,
+        normal,
+    })
+}
+
+/// First contact of the `moving` shape translated by `motion` with the still
+/// `target`, even when it goes through it.
+///
+/// Their bounds are swept first. The gap between two convex shapes moving in a
+/// straight line only shrinks then grows, so while the bounds overlap the
+/// closest approach of the shapes is searched for, then when they start and
+/// stop touching around it.
+pub fn time_of_impact(moving: &Shape, motion: Vec2, target: &Shape) -> Option<Contact> {
+    let bounds_contact = sweep(moving.bounds(), motion, target.bounds())?;
+    let moved_at = |time: f32| moving.translated(motion * time);
+    let gap_at = |time: f32| gap(&moved_at(time), target);
+    let touching_at = |time: f32| moved_at(time).intersects(target);
+    let start = bounds_contact.time;
+    let end = bounds_contact.exit_time.min(1.0);
+
+    let touching_time = if touching_at(start) {
+        start
+    } else {
+        let (mut low, mut high) = (start, end);
+        for _ in 0..TIME_OF_IMPACT_ITERATIONS {
+            let third = (high - low) / 3.0;
+            if gap_at(low + third) < gap_at(high - third) {
+                high -= third;
+            } else {
+                low += third;
+            }
+        }
+
+        let closest_time = (low + high) / 2.0;
+        if !touching_at(closest_time) {
+            return None;
         }
+        closest_time
+    };
+
+    // last time apart and first time touching
+    let (apart_time, time) = if touching_time == start {
+        (None, start)
+    } else {
+        let (apart_time, time) = bisect(start, touching_time, touching_at);
+        (Some(apart_time), time)
+    };
+    let exit_time = if touching_at(end) {
+        bounds_contact.exit_time
+    } else {
+        bisect(touching_time, end, |time| !touching_at(time)).1
+    };
+
+    // the closest features just before touching, the bounds when already in
+    // each other
+    let moved = moved_at(apart_time.unwrap_or(time));
+    let (point, normal) = Some(&moved)
+        .filter(|moved| !cores_overlap(moved, target))
+        .map(|moved| closest_approach(moved, target))
+        .filter(|(_, normal)| *normal != Vec2::ZERO)
+        .unwrap_or((bounds_contact.point, bounds_contact.normal));
+
+    Some(Contact {
+        time,
+        exit_time,
+        point,
+        normal,
+    })
+}
+
+/// Last time before and first time from which `is_past` holds, between `start`
+/// where it does not and `end` where it does
+fn bisect(mut start: f32, mut end: f32, is_past: impl Fn(f32) -> bool) -> (f32, f32) {
+    for _ in 0..TIME_OF_IMPACT_ITERATIONS {
+        let middle = (start + end) / 2.0;
+        if is_past(middle) {
+            end = middle;
+        } else {
+            start = middle;
+        }
+    }
+
+    (start, end)
+}
+
+/// Distance between two shapes, or how far they go into each other counted as
+/// their radii overlapping
+fn gap(a: &Shape, b: &Shape) -> f32 {
+    let radii = a.radius + b.radius;
+
+    if cores_overlap(a, b) {
+        -radii
+    } else {
+        core_distance(a, b) - radii
+    }
+}
+
+/// Where `b` is the closest to `a`, on its surface, and the direction out of
+/// `b` there toward `a`, when their cores do not overlap. The direction is
+/// zero when the cores touch where it cannot be told.
+fn closest_approach(a: &Shape, b: &Shape) -> (Vec2, Vec2) {
+    let mut closest = (f32::INFINITY, Vec2::ZERO, Vec2::ZERO);
+
+    for corner in a.points() {
+        for (start, end) in b.sides() {
+            let side_point = closest_point_on_segment(*corner, start, end);
+            let distance = corner.distance(side_point);
+            if distance < closest.0 {
+                closest = (distance, side_point, b.normal_at(start, end, *corner));
+            }
+        }
+    }
+    for corner in b.points() {
+        for (start, end) in a.sides() {
+            let side_point = closest_point_on_segment(*corner, start, end);
+            let distance = corner.distance(side_point);
+            if distance < closest.0 {
+                closest = (distance, *corner, -a.normal_at(start, end, *corner));
+            }
+        }
+    }
+
+    let (_, core_point, normal) = closest;
+    (core_point + normal * b.radius, normal)
+}
+
+/// Separating axis test between the cores of two shapes
+fn cores_overlap(a: &Shape, b: &Shape) -> bool {
+    if a.point_count == 1 && b.point_count == 1 {
+        return a.points[0] == b.points[0];
+    }
+
+    a.separating_axes().chain(b.separating_axes()).all(|axis| {
+        let (a_min, a_max) = a.project(axis);
+        let (b_min, b_max) = b.project(axis);
+        a_min <= b_max && b_min <= a_max
+    })
+}
+
+/// Distance between the cores of two shapes that do not overlap, always from a
+/// corner of one of them to a side of the other
+fn core_distance(a: &Shape, b: &Shape) -> f32 {
+    let corners_to_sides = |from: &Shape, to: &Shape| {
+        from.points()
+            .iter()
+            .flat_map(|point| {
+                to.sides()
+                    .map(move |(start, end)| point_to_segment_distance(*point, start, end))
+            })
+            .fold(f32::INFINITY, f32::min)
+    };
+
+    corners_to_sides(a, b).min(corners_to_sides(b, a))
+}
+
+fn point_to_segment_distance(point: Vec2, start: Vec2, end: Vec2) -> f32 {
+    point.distance(closest_point_on_segment(point, start, end))
+}
+
+fn closest_point_on_segment(point: Vec2, start: Vec2, end: Vec2) -> Vec2 {
+    let segment = end - start;
+    let length_squared = segment.length_squared();
+    if length_squared == 0.0 {
+        return start;
+    }
+
+    let progress = ((point - start).dot(segment) / length_squared).clamp(0.0, 1.0);
+    start + segment * progress
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, SQRT_2};
+
+    use bevy::ecs::system::CommandQueue;
+    use bevy::transform::transform_propagate_system;
+
+    use super::*;
+    use crate::config::GameConfig;
+    use crate::difficulty::{Difficulty, Preset};
+    use crate::pipe::{self, Pipe, PipeGap, PipeSet, PipeSetBundle, PIPE_WIDTH};
+    use crate::seed::CourseRng;
+    use crate::window::WINDOW_BOUND_LIMIT;
+
+    fn oriented_box(center: Vec2, size: Vec2, rotation: f32) -> Shape {
+        let half_x = Vec2::from_angle(rotation) * size.x / 2.0;
+        let half_y = half_x.perp().normalize() * size.y / 2.0;
+
+        Shape::parallelogram(center, half_x, half_y)
+    }
+
+    fn assert_bounds(shape: &Shape, min: Vec2, max: Vec2) {
+        let bounds = shape.bounds();
+        assert!(
+            bounds.min.abs_diff_eq(min, 1e-3) && bounds.max.abs_diff_eq(max, 1e-3),
+            "bounds {:?} to {:?}, expected {:?} to {:?}",
+            bounds.min,
+            bounds.max,
+            min,
+            max
+        );
+    }
+
+    /// The shapes of the gap and of the pipes, highest first, of a pipe set at
+    /// `position_x` moved by `transform_pipe_set` once spawned
+    fn placed_pipe_set(
+        position_x: f32,
+        transform_pipe_set: impl FnOnce(&mut Transform),
+    ) -> (Shape, [Shape; 2]) {
+        let config = GameConfig::default();
+        let difficulty = Difficulty::starting(&config, Preset::Normal);
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        PipeSetBundle::spawn(
+            &mut Commands::new(&mut queue, &world),
+            &config,
+            &difficulty,
+            &mut CourseRng::new(3),
+            position_x,
+        );
+        queue.apply(&mut world);
+
+        let mut pipe_set_query = world.query_filtered::<&mut Transform, With<PipeSet>>();
+        transform_pipe_set(&mut pipe_set_query.single_mut(&mut world));
+        SystemStage::single_threaded()
+            .with_system(transform_propagate_system)
+            .run(&mut world);
+
+        let gap = world
+            .query_filtered::<(&GlobalTransform, &Collider), With<PipeGap>>()
+            .single(&world);
+        let gap = gap.1.placed(gap.0);
+        let mut pipes: Vec<Shape> = world
+            .query_filtered::<(&GlobalTransform, &Collider), With<Pipe>>()
+            .iter(&world)
+            .map(|(transform, collider)| collider.placed(transform))
+            .collect();
+        pipes.sort_by(|a, b| b.bounds().max.y.total_cmp(&a.bounds().max.y));
+
+        (gap, pipes.try_into().unwrap())
+    }
+
+    #[test]
+    fn circle_circle() {
+        let circle = Shape::circle(Vec2::ZERO, 10.0);
+
+        assert!(circle.intersects(&Shape::circle(Vec2::new(15.0, 0.0), 5.0)));
+        assert!(circle.intersects(&Shape::circle(Vec2::new(3.0, 4.0), 1.0)));
+        assert!(circle.intersects(&Shape::circle(Vec2::ZERO, 1.0)));
+        assert!(!circle.intersects(&Shape::circle(Vec2::new(12.0, 12.0), 5.0)));
+    }
+
+    #[test]
+    fn circle_capsule() {
+        let capsule = Shape::capsule(Vec2::new(-20.0, 0.0), Vec2::new(20.0, 0.0), 5.0);
+
+        // along the side, around an end and inside
+        assert!(capsule.intersects(&Shape::circle(Vec2::new(0.0, 9.0), 4.0)));
+        assert!(capsule.intersects(&Shape::circle(Vec2::new(28.0, 0.0), 3.0)));
+        assert!(capsule.intersects(&Shape::circle(Vec2::new(10.0, 0.0), 1.0)));
+        assert!(!capsule.intersects(&Shape::circle(Vec2::new(0.0, 10.0), 4.0)));
+        // clear of the rounded end, while inside its bounding box
+        assert!(!capsule.intersects(&Shape::circle(Vec2::new(25.0, 6.0), 1.0)));
+    }
+
+    #[test]
+    fn circle_box() {
+        let square = oriented_box(Vec2::ZERO, Vec2::splat(20.0), 0.0);
+
+        assert!(square.intersects(&Shape::circle(Vec2::new(14.0, 0.0), 5.0)));
+        assert!(square.intersects(&Shape::circle(Vec2::new(2.0, -3.0), 1.0)));
+        assert!(!square.intersects(&Shape::circle(Vec2::new(16.0, 0.0), 5.0)));
+        // the corner of a box is not rounded
+        assert!(!square.intersects(&Shape::circle(Vec2::new(14.0, 14.0), 5.0)));
+        assert!(square.intersects(&Shape::circle(Vec2::new(13.0, 13.0), 5.0)));
+
+        // a corner of the turned box points at the circle
+        let diamond = oriented_box(Vec2::ZERO, Vec2::splat(20.0), FRAC_PI_4);
+        assert!(diamond.intersects(&Shape::circle(Vec2::new(18.0, 0.0), 5.0)));
+        assert!(!diamond.intersects(&Shape::circle(Vec2::new(11.0, 11.0), 5.0)));
+    }
+
+    #[test]
+    fn capsule_capsule() {
+        let capsule = Shape::capsule(Vec2::new(-20.0, 0.0), Vec2::new(20.0, 0.0), 5.0);
+
+        // crossing, with both ends away
+        assert!(capsule.intersects(&Shape::capsule(
+            Vec2::new(0.0, -30.0),
+            Vec2::new(0.0, 30.0),
+            1.0
+        )));
+        // side by side
+        assert!(capsule.intersects(&Shape::capsule(
+            Vec2::new(-10.0, 9.0),
+            Vec2::new(10.0, 9.0),
+            4.0
+        )));
+        assert!(!capsule.intersects(&Shape::capsule(
+            Vec2::new(-10.0, 10.0),
+            Vec2::new(10.0, 10.0),
+            4.0
+        )));
+        // in line
+        assert!(capsule.intersects(&Shape::capsule(
+            Vec2::new(29.0, 0.0),
+            Vec2::new(40.0, 0.0),
+            4.0
+        )));
+        assert!(!capsule.intersects(&Shape::capsule(
+            Vec2::new(31.0, 0.0),
+            Vec2::new(40.0, 0.0),
+            4.0
+        )));
+    }
+
+    #[test]
+    fn capsule_box() {
+        let pipe = oriented_box(Vec2::ZERO, Vec2::new(40.0, 200.0), 0.0);
+
+        // crossing the whole box, with both ends out of it
+        assert!(pipe.intersects(&Shape::capsule(
+            Vec2::new(-50.0, 0.0),
+            Vec2::new(50.0, 0.0),
+            1.0
+        )));
+        // reaching a side, and clear of it
+        assert!(pipe.intersects(&Shape::capsule(
+            Vec2::new(25.0, 0.0),
+            Vec2::new(40.0, 0.0),
+            5.0
+        )));
+        assert!(!pipe.intersects(&Shape::capsule(
+            Vec2::new(26.0, 0.0),
+            Vec2::new(40.0, 0.0),
+            5.0
+        )));
+        // turned toward a corner, touching it with its side
+        assert!(pipe.intersects(&Shape::capsule(
+            Vec2::new(22.0, 105.0),
+            Vec2::new(32.0, 95.0),
+            5.0
+        )));
+        assert!(!pipe.intersects(&Shape::capsule(
+            Vec2::new(23.0, 105.0),
+            Vec2::new(33.0, 95.0),
+            5.0
+        )));
+    }
+
+    #[test]
+    fn box_box() {
+        let square = oriented_box(Vec2::ZERO, Vec2::splat(20.0), 0.0);
+
+        assert!(square.intersects(&oriented_box(Vec2::new(19.0, 5.0), Vec2::splat(20.0), 0.0)));
+        assert!(square.intersects(&oriented_box(Vec2::ZERO, Vec2::splat(4.0), 0.0)));
+        assert!(!square.intersects(&oriented_box(Vec2::new(21.0, 0.0), Vec2::splat(20.0), 0.0)));
+
+        // the corner of the turned box reaches 14.1 from its center
+        let diamond = |x: f32, y: f32| oriented_box(Vec2::new(x, y), Vec2::splat(20.0), FRAC_PI_4);
+        assert!(square.intersects(&diamond(24.0, 0.0)));
+        assert!(!square.intersects(&diamond(25.0, 0.0)));
+        // overlapping bounding boxes, separated along the turned sides
+        assert!(!square.intersects(&diamond(20.0, 20.0)));
+    }
+
+    #[test]
+    fn sweep_through_a_thin_wall() {
+        let flappy = Rect {
+            min: Vec2::new(-10.0, -10.0),
+            max: Vec2::new(10.0, 10.0),
+        };
+        let wall = Rect {
+            min: Vec2::new(40.0, -50.0),
+            max: Vec2::new(42.0, 50.0),
+        };
+
+        // neither the start nor the end touches the wall
+        let contact = sweep(flappy, Vec2::new(100.0, 20.0), wall).unwrap();
+        assert!((contact.time - 0.3).abs() < 1e-5);
+        assert!((contact.exit_time - 0.52).abs() < 1e-5);
+        assert_eq!(contact.normal, Vec2::new(-1.0, 0.0));
+        assert!(contact.point.abs_diff_eq(Vec2::new(40.0, 6.0), 1e-4));
+
+        assert_eq!(sweep(flappy, Vec2::new(25.0, 0.0), wall), None);
+        assert_eq!(sweep(flappy, Vec2::new(100.0, 250.0), wall), None);
+    }
+
+    #[test]
+    fn sweep_onto_the_floor() {
+        let flappy = Rect {
+            min: Vec2::new(-10.0, 0.0),
+            max: Vec2::new(10.0, 20.0),
+        };
+        let floor = Rect {
+            min: Vec2::new(-100.0, -30.0),
+            max: Vec2::new(100.0, -10.0),
+        };
+
+        let contact = sweep(flappy, Vec2::new(5.0, -40.0), floor).unwrap();
+        assert!((contact.time - 0.25).abs() < 1e-5);
+        assert_eq!(contact.normal, Vec2::new(0.0, 1.0));
+        assert!(contact.point.abs_diff_eq(Vec2::new(1.25, -10.0), 1e-4));
+
+        // sliding along it, touching from the start
+        let resting = Rect {
+            min: Vec2::new(-10.0, -12.0),
+            max: Vec2::new(10.0, 8.0),
+        };
+        let contact = sweep(resting, Vec2::new(10.0, 0.0), floor).unwrap();
+        assert_eq!(contact.time, 0.0);
+        assert_eq!(contact.normal, Vec2::new(0.0, 1.0));
+        assert!(contact.exit_time > 1.0);
+    }
+
+    #[test]
+    fn shapes_touching_only_mid_step() {
+        // grazes the top corner of a diamond, past it by the end of the step
+        let diamond = oriented_box(Vec2::ZERO, Vec2::splat(20.0 * SQRT_2), FRAC_PI_4);
+        let circle = Shape::circle(Vec2::new(-60.0, 22.0), 5.0);
+        let motion = Vec2::new(80.0, 0.0);
+        assert!(sweep(circle.bounds(), motion, diamond.bounds()).is_some());
+        assert!(!circle.translated(motion).intersects(&diamond));
+
+        let contact = time_of_impact(&circle, motion, &diamond).unwrap();
+        let touch_x = 2.0 - 5.0 * SQRT_2;
+        assert!((contact.time - (touch_x + 60.0) / 80.0).abs() < 1e-4);
+        assert!((contact.exit_time - (-touch_x + 60.0) / 80.0).abs() < 1e-4);
+        assert!(contact
+            .normal
+            .abs_diff_eq(Vec2::new(-1.0, 1.0) / SQRT_2, 1e-4));
+        assert!(contact
+            .point
+            .abs_diff_eq(Vec2::new(touch_x, 22.0) - contact.normal * 5.0, 1e-3));
+        assert!(circle
+            .translated(motion * contact.time)
+            .intersects(&diamond));
+
+        // stops short, in the bounds of the diamond but clear of its side
+        let short_motion = Vec2::new(40.0, 0.0);
+        assert!(sweep(circle.bounds(), short_motion, diamond.bounds()).is_some());
+        assert_eq!(time_of_impact(&circle, short_motion, &diamond), None);
+    }
+
+    #[test]
+    fn shapes_touching_from_the_start() {
+        let floor = oriented_box(Vec2::new(0.0, -20.0), Vec2::new(100.0, 20.0), 0.0);
+        let resting = Shape::capsule(Vec2::new(-5.0, 0.0), Vec2::new(5.0, 0.0), 10.0);
+
+        let contact = time_of_impact(&resting, Vec2::new(10.0, 0.0), &floor).unwrap();
+        assert_eq!(contact.time, 0.0);
+        assert!(contact.exit_time > 1.0);
+        assert_eq!(contact.normal, Vec2::new(0.0, 1.0));
+
+        // a tilted capsule falling onto the floor lands on its low end
+        let tilted = Shape::capsule(Vec2::new(-10.0, 30.0), Vec2::new(10.0, 40.0), 5.0);
+        let contact = time_of_impact(&tilted, Vec2::new(0.0, -40.0), &floor).unwrap();
+        assert!((contact.time - 35.0 / 40.0).abs() < 1e-4);
+        assert!(contact.normal.abs_diff_eq(Vec2::new(0.0, 1.0), 1e-4));
+        assert!(contact.point.abs_diff_eq(Vec2::new(-10.0, -10.0), 1e-3));
+    }
+
+    #[test]
+    fn collider_turns_with_its_entity() {
+        let capsule = Collider::capsule(40.0, 5.0);
+        let wall = oriented_box(Vec2::new(0.0, 30.0), Vec2::new(100.0, 20.0), 0.0);
+
+        assert!(!capsule
+            .placed(&GlobalTransform::identity())
+            .intersects(&wall));
+        assert!(capsule
+            .placed(&Transform::from_rotation(Quat::from_rotation_z(FRAC_PI_2)).into())
+            .intersects(&wall));
+
+        // the offset turns along
+        let turned_box = Collider::new(Vec2::new(20.0, 10.0))
+            .with_offset(Vec2::new(10.0, 0.0))
+            .placed(
+                &Transform {
+                    translation: Vec3::new(5.0, 0.0, 0.0),
+                    rotation: Quat::from_rotation_z(FRAC_PI_2),
+                    ..default()
+                }
+                .into(),
+            );
+        assert_bounds(&turned_box, Vec2::new(0.0, 0.0), Vec2::new(10.0, 20.0));
+    }
+
+    #[test]
+    fn collider_scales_with_its_entity() {
+        let scaled = GlobalTransform::from(Transform {
+            translation: Vec3::new(100.0, 0.0, 0.0),
+            scale: Vec3::new(2.0, 3.0, 0.0),
+            ..default()
+        });
+
+        let sprite_box = Collider::new(Vec2::ONE).with_offset(Vec2::new(1.0, 1.0));
+        assert_bounds(
+            &sprite_box.placed(&scaled),
+            Vec2::new(101.0, 1.5),
+            Vec2::new(103.0, 4.5),
+        );
+
+        // round shapes take the largest scale
+        let circle = Collider::circle(2.0);
+        assert_bounds(
+            &circle.placed(&scaled),
+            Vec2::new(94.0, -6.0),
+            Vec2::new(106.0, 6.0),
+        );
     }
 
-    pub fn position(&self) -> &Vec3 {
-        &self.position
+    #[test]
+    fn pipe_set_children_cover_their_sprites() {
+        let config = GameConfig::default();
+        let pipe_gap = Difficulty::starting(&config, Preset::Normal).pipe_gap;
+        let (gap, [top_pipe, bottom_pipe]) = placed_pipe_set(500.0, |_| {});
+
+        let gap_bounds = gap.bounds();
+        let gap_center_y = (gap_bounds.min.y + gap_bounds.max.y) / 2.0;
+        let gap_half_width = pipe::gap_width(pipe_gap) / 2.0;
+        assert_bounds(
+            &gap,
+            Vec2::new(500.0 - gap_half_width, gap_center_y - pipe_gap / 2.0),
+            Vec2::new(500.0 + gap_half_width, gap_center_y + pipe_gap / 2.0),
+        );
+
+        // the pipes reach out of the window from the edges of the gap
+        let window_top = config.window_height / 2.0 + WINDOW_BOUND_LIMIT;
+        assert_bounds(
+            &top_pipe,
+            Vec2::new(500.0 - PIPE_WIDTH / 2.0, gap_bounds.max.y),
+            Vec2::new(500.0 + PIPE_WIDTH / 2.0, window_top),
+        );
+        assert_bounds(
+            &bottom_pipe,
+            Vec2::new(500.0 - PIPE_WIDTH / 2.0, -window_top),
+            Vec2::new(500.0 + PIPE_WIDTH / 2.0, gap_bounds.min.y),
+        );
     }
 
-    pub fn scale(&self) -> &Vec2 {
-        &self.scale
+    #[test]
+    fn pipe_set_children_follow_their_parent() {
+        let (gap, [top_pipe, bottom_pipe]) = placed_pipe_set(500.0, |_| {});
+        let (moved_gap, [moved_top_pipe, moved_bottom_pipe]) =
+            placed_pipe_set(500.0, |transform| {
+                transform.translation.x = 200.0;
+                transform.scale = Vec3::new(2.0, 0.5, 1.0);
+            });
+
+        // scaled around the center of the set, then moved
+        let scaled = |shape: &Shape| {
+            let bounds = shape.bounds();
+            let scale = Vec2::new(2.0, 0.5);
+            (
+                (bounds.min - Vec2::new(500.0, 0.0)) * scale + Vec2::new(200.0, 0.0),
+                (bounds.max - Vec2::new(500.0, 0.0)) * scale + Vec2::new(200.0, 0.0),
+            )
+        };
+        for (shape, moved_shape) in [
+            (gap, moved_gap),
+            (top_pipe, moved_top_pipe),
+            (bottom_pipe, moved_bottom_pipe),
+        ] {
+            let (min, max) = scaled(&shape);
+            assert_bounds(&moved_shape, min, max);
+        }
+
+        // turned a quarter, the pipes reach out on each side of the gap, as
+        // wide as the set was
+        let (turned_gap, turned_pipes) = placed_pipe_set(500.0, |transform| {
+            transform.rotation = Quat::from_rotation_z(FRAC_PI_2);
+        });
+        let turned_gap_bounds = turned_gap.bounds();
+        let [left_pipe, right_pipe] = turned_pipes.map(|pipe| pipe.bounds());
+        let [left_pipe, right_pipe] = if left_pipe.min.x < right_pipe.min.x {
+            [left_pipe, right_pipe]
+        } else {
+            [right_pipe, left_pipe]
+        };
+        assert!((left_pipe.max.x - turned_gap_bounds.min.x).abs() < 1e-3);
+        assert!((right_pipe.min.x - turned_gap_bounds.max.x).abs() < 1e-3);
+        for bounds in [left_pipe, right_pipe] {
+            assert!((bounds.min.y + PIPE_WIDTH / 2.0).abs() < 1e-3);
+            assert!((bounds.max.y - PIPE_WIDTH / 2.0).abs() < 1e-3);
+        }
     }
 }
@@ -0,0 +1,716 @@
+use bevy::prelude::*;
+use bevy::sprite::Rect;
+use bevy::transform::TransformSystem;
+
+use crate::collider::{self, Collider, CollisionLayers, Contact, Shape};
+use crate::game_state::{while_playing, GameState};
+use crate::physics::{PhysicsStage, PreviousTranslation};
+use crate::velocity::ApplyVelocitySystem;
+
+/// Sent by the physics step where two enabled colliders on each other's mask
+/// start touching.
+///
+/// The first entity is a moving one, with a `PreviousTranslation`, swept from
+/// where it was at the start of the step so it cannot go through the other
+/// one unnoticed. The `contact` is where it first touched the other one, where
+/// it stopped when both are solid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CollisionStarted {
+    pub entities: [Entity; 2],
+    pub layers: [CollisionLayers; 2],
+    pub contact: Contact,
+}
+
+/// Sent by the physics step where two colliders stop touching, also when one
+/// of them is disabled or despawned
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CollisionEnded {
+    pub entities: [Entity; 2],
+    pub layers: [CollisionLayers; 2],
+}
+
+impl CollisionStarted {
+    /// The entities on one of `layers` and on one of `other_layers`, in that
+    /// order, when the collision is between them
+    pub fn between(
+        &self,
+        layers: CollisionLayers,
+        other_layers: CollisionLayers,
+    ) -> Option<(Entity, Entity)> {
+        let [first, second] = self.entities;
+        let [first_layers, second_layers] = self.layers;
+
+        if first_layers.intersects(layers) && second_layers.intersects(other_layers) {
+            Some((first, second))
+        } else if second_layers.intersects(layers) && first_layers.intersects(other_layers) {
+            Some((second, first))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+impl CollisionStarted {
+    /// Two entities touching from the start of a step, sent to the systems
+    /// reacting to collisions in their tests
+    pub fn overlapping(entities: [Entity; 2], layers: [CollisionLayers; 2]) -> Self {
+        CollisionStarted {
+            entities,
+            layers,
+            contact: Contact {
+                time: 0.0,
+                exit_time: 1.0,
+                point: Vec2::ZERO,
+                normal: Vec2::Y,
+            },
+        }
+    }
+}
+
+/// Finds the collisions of every physics step, after everything has moved.
+/// Systems reacting to them run `.after(CollisionSystem)` in the `PhysicsStage`,
+/// to apply them before the next step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+pub struct CollisionSystem;
+
+pub struct CollisionPlugin;
+
+impl Plugin for CollisionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Touching>();
+        app.add_event::<CollisionStarted>();
+        app.add_event::<CollisionEnded>();
+        app.add_system_to_stage(
+            PhysicsStage,
+            detect_collisions
+                .with_run_criteria(while_playing)
+                .label(CollisionSystem)
+                .after(TransformSystem::TransformPropagate)
+                .after(ApplyVelocitySystem),
+        );
+        app.add_system_set(SystemSet::on_enter(GameState::Playing).with_system(clear_touching));
+        app.add_system(log_collisions);
+    }
+}
+
+/// Pairs of colliders touching at the end of the last physics step, each one
+/// in entity order to be found again whichever entity the queries return first
+#[derive(Default)]
+struct Touching(Vec<([Entity; 2], [CollisionLayers; 2])>);
+
+impl Touching {
+    fn pair(
+        entities: [Entity; 2],
+        layers: [CollisionLayers; 2],
+    ) -> ([Entity; 2], [CollisionLayers; 2]) {
+        if entities[0] <= entities[1] {
+            (entities, layers)
+        } else {
+            ([entities[1], entities[0]], [layers[1], layers[0]])
+        }
+    }
+
+    fn contains(&self, entities: &[Entity; 2]) -> bool {
+        self.0.iter().any(|(pair, _)| pair == entities)
+    }
+}
+
+/// A collider over a physics step
+pub struct Body {
+    entity: Entity,
+    layers: CollisionLayers,
+    mask: CollisionLayers,
+    sensor: bool,
+    /// At the start of the step
+    shape: Shape,
+    /// `None` for a still body, never checked against other still ones
+    motion: Option<Vec2>,
+}
+
+impl Body {
+    pub fn moving(entity: Entity, collider: &Collider, shape: Shape, motion: Vec2) -> Self {
+        Self::new(entity, collider, shape, Some(motion))
+    }
+
+    pub fn still(entity: Entity, collider: &Collider, shape: Shape) -> Self {
+        Self::new(entity, collider, shape, None)
+    }
+
+    fn new(entity: Entity, collider: &Collider, shape: Shape, motion: Option<Vec2>) -> Self {
+        Body {
+            entity,
+            layers: collider.layers(),
+            mask: collider.mask(),
+            sensor: collider.is_sensor(),
+            shape,
+            motion,
+        }
+    }
+
+    fn motion(&self) -> Vec2 {
+        self.motion.unwrap_or(Vec2::ZERO)
+    }
+
+    /// Whether the two bodies are worth checking against each other
+    fn may_collide_with(&self, other: &Body) -> bool {
+        (self.motion.is_some() || other.motion.is_some())
+            && self.mask.intersects(other.layers)
+            && other.mask.intersects(self.layers)
+    }
+
+    /// Bounds of everywhere the body goes along the step
+    fn swept_bounds(&self) -> Rect {
+        let bounds = self.shape.bounds();
+        let motion = self.motion();
+
+        Rect {
+            min: bounds.min + motion.min(Vec2::ZERO),
+            max: bounds.max + motion.max(Vec2::ZERO),
+        }
+    }
+
+    /// Where the two bodies first touch along the step, if they do
+    fn contact(&self, other: &Body) -> Option<Contact> {
+        let contact =
+            collider::time_of_impact(&self.shape, self.motion() - other.motion(), &other.shape)?;
+
+        // seen from the still one, which moved too
+        Some(Contact {
+            point: contact.point + other.motion() * contact.time,
+            ..contact
+        })
+    }
+}
+
+/// Indices of the pairs of `bodies` that may touch along the step, lowest
+/// first. Sweeps the bodies from left to right, the course scrolling along x,
+/// so each one is only checked against the few whose swept bounds reach it.
+pub fn broadphase_pairs(bodies: &[Body]) -> Vec<(usize, usize)> {
+    let swept_bounds: Vec<Rect> = bodies.iter().map(Body::swept_bounds).collect();
+    let mut order: Vec<usize> = (0..bodies.len()).collect();
+    order.sort_unstable_by(|a, b| swept_bounds[*a].min.x.total_cmp(&swept_bounds[*b].min.x));
+
+    let mut pairs = Vec::new();
+    for (position, &index) in order.iter().enumerate() {
+        let bounds = swept_bounds[index];
+
+        for &other_index in &order[position + 1..] {
+            let other_bounds = swept_bounds[other_index];
+            if other_bounds.min.x > bounds.max.x {
+                break;
+            }
+
+            if other_bounds.min.y <= bounds.max.y
+                && other_bounds.max.y >= bounds.min.y
+                && bodies[index].may_collide_with(&bodies[other_index])
+            {
+                pairs.push((index.min(other_index), index.max(other_index)));
+            }
+        }
+    }
+
+    // sorted like the brute force pairs, so both give the same events in the
+    // same order
+    pairs.sort_unstable();
+    pairs
+}
+
+/// Indices of every pair of `bodies` on each other's mask, lowest first
+pub fn brute_force_pairs(bodies: &[Body]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    for (index, body) in bodies.iter().enumerate() {
+        for (other_index, other) in bodies.iter().enumerate().skip(index + 1) {
+            if body.may_collide_with(other) {
+                pairs.push((index, other_index));
+            }
+        }
+    }
+
+    pairs
+}
+
+/// The `pairs` of `bodies` touching along the step, with where they first do
+pub fn contacts(
+    bodies: &[Body],
+    pairs: Vec<(usize, usize)>,
+) -> impl Iterator<Item = (usize, usize, Contact)> + '_ {
+    pairs.into_iter().filter_map(|(index, other_index)| {
+        let contact = bodies[index].contact(&bodies[other_index])?;
+
+        Some((index, other_index, contact))
+    })
+}
+
+//
+// -- SYSTEM
+//
+
+fn detect_collisions(
+    mut touching: ResMut<Touching>,
+    mut started_events: EventWriter<CollisionStarted>,
+    mut ended_events: EventWriter<CollisionEnded>,
+    mut moving_query: Query<(Entity, &mut Transform, &PreviousTranslation, &Collider)>,
+    still_query: Query<(Entity, &GlobalTransform, &Collider), Without<PreviousTranslation>>,
+) {
+    // moving bodies are top level entities, their `GlobalTransform` is only
+    // propagated at the start of the step. They come first, before the still
+    // ones, so a pair with one starts with it.
+    let mut bodies: Vec<Body> = moving_query
+        .iter()
+        .filter(|(_, _, _, collider)| collider.enabled)
+        .map(|(entity, transform, previous_translation, collider)| {
+            let start = Transform {
+                translation: previous_translation.0,
+                ..*transform
+            };
+
+            Body::moving(
+                entity,
+                collider,
+                collider.placed(&start.into()),
+                (transform.translation - start.translation).truncate(),
+            )
+        })
+        .collect();
+    let moving_body_count = bodies.len();
+    bodies.extend(
+        still_query
+            .iter()
+            .filter(|(_, _, collider)| collider.enabled)
+            .map(|(entity, transform, collider)| {
+                Body::still(entity, collider, collider.placed(transform))
+            }),
+    );
+
+    // how far along the step each moving body goes before its shape hits
+    // something solid
+    let mut stop_times = vec![1.0_f32; moving_body_count];
+    let mut now_touching = Touching::default();
+    for (index, other_index, contact) in contacts(&bodies, broadphase_pairs(&bodies)) {
+        let (body, other) = (&bodies[index], &bodies[other_index]);
+
+        if !body.sensor && !other.sensor {
+            for stopped_index in [index, other_index] {
+                if let Some(stop_time) = stop_times.get_mut(stopped_index) {
+                    *stop_time = stop_time.min(contact.time);
+                }
+            }
+        }
+
+        let entities = [body.entity, other.entity];
+        let layers = [body.layers, other.layers];
+        let pair = Touching::pair(entities, layers);
+        if !touching.contains(&pair.0) {
+            started_events.send(CollisionStarted {
+                entities,
+                layers,
+                contact,
+            });
+        }
+        now_touching.0.push(pair);
+    }
+
+    for (body, stop_time) in bodies.iter().zip(stop_times) {
+        if stop_time < 1.0 {
+            let (_, mut transform, _, _) = moving_query.get_mut(body.entity).unwrap();
+            transform.translation -= (body.motion() * (1.0 - stop_time)).extend(0.0);
+        }
+    }
+
+    for (entities, layers) in &touching.0 {
+        if !now_touching.contains(entities) {
+            ended_events.send(CollisionEnded {
+                entities: *entities,
+                layers: *layers,
+            });
+        }
+    }
+    *touching = now_touching;
+}
+
+/// A run starts with nothing touching, the crash ending the last one is never
+/// seen ending
+fn clear_touching(mut touching: ResMut<Touching>) {
+    touching.0.clear();
+}
+
+fn log_collisions(
+    mut started_events: EventReader<CollisionStarted>,
+    mut ended_events: EventReader<CollisionEnded>,
+) {
+    for event in started_events.iter() {
+        debug!(
+            "{:?} {:?} started touching {:?} {:?} at {}",
+            event.layers[0],
+            event.entities[0],
+            event.layers[1],
+            event.entities[1],
+            event.contact.point
+        );
+    }
+    for event in ended_events.iter() {
+        debug!(
+            "{:?} {:?} stopped touching {:?} {:?}",
+            event.layers[0], event.entities[0], event.layers[1], event.entities[1]
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_4, SQRT_2, TAU};
+
+    use rand::Rng;
+
+    use super::*;
+    use crate::seed::CourseRng;
+
+    #[derive(Component)]
+    struct Tagged;
+
+    /// A world stepping `detect_collisions` alone
+    struct Steps {
+        world: World,
+        stage: SystemStage,
+    }
+
+    impl Steps {
+        fn new() -> Self {
+            let mut world = World::new();
+            world.init_resource::<Touching>();
+            world.init_resource::<Events<CollisionStarted>>();
+            world.init_resource::<Events<CollisionEnded>>();
+
+            Steps {
+                world,
+                stage: SystemStage::single_threaded().with_system(detect_collisions),
+            }
+        }
+
+        fn spawn_moving(&mut self, collider: Collider, position: Vec2) -> Entity {
+            self.world
+                .spawn()
+                .insert(collider)
+                .insert(Transform::from_translation(position.extend(1.0)))
+                .insert(PreviousTranslation(position.extend(1.0)))
+                .id()
+        }
+
+        fn spawn_still(&mut self, collider: Collider, position: Vec2) -> Entity {
+            self.world
+                .spawn()
+                .insert(collider)
+                .insert(GlobalTransform::from_translation(position.extend(1.0)))
+                .id()
+        }
+
+        /// Moves the entity over the next step
+        fn move_to(&mut self, entity: Entity, position: Vec2) {
+            self.world.get_mut::<Transform>(entity).unwrap().translation = position.extend(1.0);
+        }
+
+        fn position(&self, entity: Entity) -> Vec2 {
+            self.world
+                .get::<Transform>(entity)
+                .unwrap()
+                .translation
+                .truncate()
+        }
+
+        fn set_enabled(&mut self, entity: Entity, enabled: bool) {
+            self.world.get_mut::<Collider>(entity).unwrap().enabled = enabled;
+        }
+
+        /// Runs a step, the moved entities going from where they were at the
+        /// end of the last one
+        fn step(&mut self) -> (Vec<CollisionStarted>, Vec<CollisionEnded>) {
+            self.stage.run(&mut self.world);
+            for (transform, mut previous_translation) in self
+                .world
+                .query::<(&Transform, &mut PreviousTranslation)>()
+                .iter_mut(&mut self.world)
+            {
+                previous_translation.0 = transform.translation;
+            }
+
+            let started = self
+                .world
+                .resource_mut::<Events<CollisionStarted>>()
+                .drain()
+                .collect();
+            let ended = self
+                .world
+                .resource_mut::<Events<CollisionEnded>>()
+                .drain()
+                .collect();
+            (started, ended)
+        }
+    }
+
+    fn player() -> Collider {
+        Collider::circle(10.0).with_layers(CollisionLayers::PLAYER)
+    }
+
+    fn score_trigger() -> Collider {
+        Collider::new(Vec2::splat(40.0))
+            .with_layers(CollisionLayers::SCORE_TRIGGER)
+            .into_sensor()
+    }
+
+    #[test]
+    fn started_once_while_touching() {
+        let mut steps = Steps::new();
+        let player = steps.spawn_moving(player(), Vec2::new(-100.0, 0.0));
+        let trigger = steps.spawn_still(score_trigger(), Vec2::ZERO);
+
+        steps.move_to(player, Vec2::new(-60.0, 0.0));
+        assert_eq!(steps.step().0.len(), 0);
+
+        steps.move_to(player, Vec2::new(-20.0, 0.0));
+        let (started, ended) = steps.step();
+        assert_eq!(started.len(), 1);
+        assert_eq!(started[0].entities, [player, trigger]);
+        assert_eq!(
+            started[0].between(CollisionLayers::SCORE_TRIGGER, CollisionLayers::PLAYER),
+            Some((trigger, player))
+        );
+        assert!(ended.is_empty());
+
+        // still touching at the start of the step it leaves
+        for position in [Vec2::new(0.0, 0.0), Vec2::new(100.0, 0.0)] {
+            steps.move_to(player, position);
+            let (started, ended) = steps.step();
+            assert!(started.is_empty() && ended.is_empty());
+        }
+
+        steps.move_to(player, Vec2::new(200.0, 0.0));
+        let (started, ended) = steps.step();
+        assert!(started.is_empty());
+        assert_eq!(ended.len(), 1);
+        assert_eq!(ended[0].entities, [player, trigger]);
+    }
+
+    #[test]
+    fn ended_when_disabled_or_despawned() {
+        let mut steps = Steps::new();
+        let player = steps.spawn_moving(player(), Vec2::ZERO);
+        let trigger = steps.spawn_still(score_trigger(), Vec2::ZERO);
+        assert_eq!(steps.step().0.len(), 1);
+
+        steps.set_enabled(trigger, false);
+        assert_eq!(steps.step().1.len(), 1);
+        assert_eq!(steps.step(), (vec![], vec![]));
+
+        steps.set_enabled(trigger, true);
+        assert_eq!(steps.step().0.len(), 1);
+
+        steps.set_enabled(player, false);
+        assert_eq!(steps.step().1.len(), 1);
+        steps.set_enabled(player, true);
+        assert_eq!(steps.step().0.len(), 1);
+
+        steps.world.despawn(trigger);
+        let (started, ended) = steps.step();
+        assert!(started.is_empty());
+        assert_eq!(ended.len(), 1);
+        assert_eq!(ended[0].entities, [player, trigger]);
+    }
+
+    #[test]
+    fn moving_pairs_found_again_in_any_order() {
+        let mut steps = Steps::new();
+        let first = steps.spawn_moving(player(), Vec2::ZERO);
+        let second = steps.spawn_moving(Collider::circle(10.0), Vec2::new(15.0, 0.0));
+        let mut entities = steps.world.query_filtered::<Entity, With<Collider>>();
+        assert_eq!(
+            entities.iter(&steps.world).collect::<Vec<_>>(),
+            [first, second]
+        );
+        assert_eq!(steps.step().0.len(), 1);
+
+        // moved to another archetype, the first one now comes last
+        steps.world.entity_mut(first).insert(Tagged);
+        assert_eq!(
+            entities.iter(&steps.world).collect::<Vec<_>>(),
+            [second, first]
+        );
+        assert_eq!(steps.step(), (vec![], vec![]));
+    }
+
+    #[test]
+    fn nothing_ends_across_runs() {
+        let mut steps = Steps::new();
+        let player = steps.spawn_moving(player(), Vec2::ZERO);
+        steps.spawn_still(
+            Collider::new(Vec2::new(100.0, 20.0)).with_layers(CollisionLayers::GROUND),
+            Vec2::new(0.0, -20.0),
+        );
+        assert_eq!(steps.step().0.len(), 1);
+
+        SystemStage::single_threaded()
+            .with_system(clear_touching)
+            .run(&mut steps.world);
+        steps
+            .world
+            .get_mut::<Transform>(player)
+            .unwrap()
+            .translation = Vec3::new(0.0, 100.0, 1.0);
+        steps
+            .world
+            .get_mut::<PreviousTranslation>(player)
+            .unwrap()
+            .0 = Vec3::new(0.0, 100.0, 1.0);
+        assert_eq!(steps.step(), (vec![], vec![]));
+    }
+
+    #[test]
+    fn masks_exclude_pairs() {
+        let mut steps = Steps::new();
+        // like the ghost, only on the mask of the triggers
+        let ghost = steps.spawn_moving(
+            player().with_mask(CollisionLayers::SCORE_TRIGGER),
+            Vec2::ZERO,
+        );
+        let player = steps.spawn_moving(player(), Vec2::new(0.0, 100.0));
+        steps.spawn_still(Collider::new(Vec2::splat(40.0)), Vec2::new(50.0, 0.0));
+        steps.spawn_still(
+            Collider::new(Vec2::splat(40.0)).with_mask(CollisionLayers::GROUND),
+            Vec2::new(50.0, 100.0),
+        );
+
+        steps.move_to(ghost, Vec2::new(100.0, 0.0));
+        steps.move_to(player, Vec2::new(100.0, 100.0));
+        assert_eq!(steps.step(), (vec![], vec![]));
+        assert_eq!(steps.position(ghost), Vec2::new(100.0, 0.0));
+        assert_eq!(steps.position(player), Vec2::new(100.0, 100.0));
+    }
+
+    #[test]
+    fn sensors_do_not_stop_bodies() {
+        let mut steps = Steps::new();
+        let player = steps.spawn_moving(player(), Vec2::ZERO);
+        let trigger = steps.spawn_still(score_trigger(), Vec2::new(50.0, 0.0));
+
+        steps.move_to(player, Vec2::new(100.0, 0.0));
+        let (started, _) = steps.step();
+        assert_eq!(started.len(), 1);
+        assert_eq!(started[0].entities, [player, trigger]);
+        assert_eq!(steps.position(player), Vec2::new(100.0, 0.0));
+    }
+
+    #[test]
+    fn solids_stop_bodies_where_their_shapes_touch() {
+        let mut steps = Steps::new();
+        let player = steps.spawn_moving(player(), Vec2::new(0.0, 10.0));
+        // a diamond, its bounds reached well before its side
+        let diamond = steps
+            .world
+            .spawn()
+            .insert(Collider::new(Vec2::splat(20.0 * SQRT_2)))
+            .insert(GlobalTransform::from(Transform {
+                translation: Vec3::new(100.0, 0.0, 1.0),
+                rotation: Quat::from_rotation_z(FRAC_PI_4),
+                ..default()
+            }))
+            .id();
+
+        steps.move_to(player, Vec2::new(200.0, 10.0));
+        let (started, _) = steps.step();
+        assert_eq!(started.len(), 1);
+        assert_eq!(started[0].entities, [player, diamond]);
+        assert!(started[0]
+            .contact
+            .normal
+            .abs_diff_eq(Vec2::new(-1.0, 1.0) / SQRT_2, 1e-3));
+
+        let stop_x = 90.0 - 10.0 * SQRT_2;
+        assert!(steps
+            .position(player)
+            .abs_diff_eq(Vec2::new(stop_x, 10.0), 1e-2));
+    }
+
+    #[test]
+    fn broadphase_finds_every_contact() {
+        let mut course_rng = CourseRng::new(7);
+        let rng = course_rng.rng();
+        let layers = [
+            CollisionLayers::PLAYER,
+            CollisionLayers::OBSTACLE,
+            CollisionLayers::SCORE_TRIGGER,
+            CollisionLayers::PICKUP,
+            CollisionLayers::GROUND,
+        ];
+
+        let mut bodies: Vec<Body> = (0..300)
+            .map(|index| {
+                let collider = match index % 3 {
+                    0 => Collider::circle(rng.gen_range(5.0..30.0)),
+                    1 => Collider::capsule(rng.gen_range(0.0..60.0), rng.gen_range(5.0..20.0)),
+                    _ => Collider::new(Vec2::new(
+                        rng.gen_range(5.0..150.0),
+                        rng.gen_range(5.0..150.0),
+                    )),
+                }
+                .with_layers(layers[rng.gen_range(0..layers.len())])
+                .with_mask(if rng.gen_bool(0.8) {
+                    CollisionLayers::ALL
+                } else {
+                    layers[rng.gen_range(0..layers.len())]
+                });
+                let shape = collider.placed(
+                    &Transform {
+                        translation: Vec3::new(
+                            rng.gen_range(0.0..3000.0),
+                            rng.gen_range(-350.0..350.0),
+                            1.0,
+                        ),
+                        rotation: Quat::from_rotation_z(rng.gen_range(0.0..TAU)),
+                        ..default()
+                    }
+                    .into(),
+                );
+
+                let entity = Entity::from_raw(index);
+                // fast enough to go through things
+                if index < 30 {
+                    let motion =
+                        Vec2::new(rng.gen_range(-300.0..300.0), rng.gen_range(-300.0..300.0));
+                    Body::moving(entity, &collider, shape, motion)
+                } else {
+                    Body::still(entity, &collider, shape)
+                }
+            })
+            .collect();
+        // and two moving bodies meeting halfway
+        let collider = Collider::circle(10.0);
+        for (index, (position, motion)) in [
+            (Vec2::new(1000.0, 500.0), Vec2::new(100.0, 0.0)),
+            (Vec2::new(1200.0, 500.0), Vec2::new(-100.0, 0.0)),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let shape = collider.placed(&GlobalTransform::from_translation(position.extend(1.0)));
+            bodies.insert(
+                0,
+                Body::moving(
+                    Entity::from_raw(1000 + index as u32),
+                    &collider,
+                    shape,
+                    motion,
+                ),
+            );
+        }
+
+        let touching = |pairs| {
+            contacts(&bodies, pairs)
+                .map(|(index, other_index, _)| (index, other_index))
+                .collect::<Vec<_>>()
+        };
+        let brute_force_contacts = touching(brute_force_pairs(&bodies));
+        assert!(brute_force_contacts.len() > 30);
+        assert!(brute_force_contacts.contains(&(0, 1)));
+        assert_eq!(touching(broadphase_pairs(&bodies)), brute_force_contacts);
+    }
+}
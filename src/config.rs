@@ -0,0 +1,467 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use bevy::asset::{AssetLoader, FileAssetIo, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::difficulty::{DifficultyCurve, Preset, PresetTunings};
+use crate::pipe::PIPE_WIDTH;
+
+const ASSET_FOLDER: &str = "assets";
+const GAME_CONFIG_PATH: &str = "game.config.ron";
+
+/// Gameplay tuning, read from `assets/game.config.ron`. Missing fields keep
+/// their default value, unknown fields are rejected to catch typos.
+///
+/// The file is watched when rendering: saving it applies the new values to the
+/// running game, pipes and floors already spawned keep their previous shape.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "8f3b6f3e-4d1c-4f6a-9a57-2c1d3e0b7a41"]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig {
+    pub window_width: f32,
+    pub window_height: f32,
+    /// Forward velocity of flappy and the camera, in pixels per second
+    pub scrolling_speed: f32,
+    pub floor_thickness: f32,
+    /// Physics and course of each difficulty preset
+    pub presets: PresetTunings,
+    /// Ramp of the scrolling speed, pipe gap and pipe distance with the score
+    pub difficulty: DifficultyCurve,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            window_width: 500.0,
+            window_height: 700.0,
+            scrolling_speed: 150.0,
+            floor_thickness: 30.0,
+            presets: PresetTunings::default(),
+            difficulty: DifficultyCurve::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, std::io::Error),
+    Parse(ron::Error),
+    /// Every value that is out of its range
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, err) => write!(f, "could not read {}: {}", path.display(), err),
+            ConfigError::Parse(err) => {
+                write!(f, "{} is not a valid config: {}", GAME_CONFIG_PATH, err)
+            }
+            ConfigError::Invalid(problems) => {
+                write!(f, "{} has invalid values:", GAME_CONFIG_PATH)?;
+                for problem in problems {
+                    write!(f, "\n  - {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+impl GameConfig {
+    /// Read the config file of the assets folder, the same one the
+    /// `AssetServer` watches
+    pub fn load() -> Result<Self, ConfigError> {
+        let path = FileAssetIo::get_base_path()
+            .join(ASSET_FOLDER)
+            .join(GAME_CONFIG_PATH);
+        let content = fs::read(&path).map_err(|err| ConfigError::Read(path, err))?;
+
+        Self::from_bytes(&content)
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, ConfigError> {
+        let config: GameConfig = ron::de::from_bytes(bytes).map_err(ConfigError::Parse)?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        let mut check = |is_valid: bool, problem: String| {
+            if !is_valid {
+                problems.push(problem);
+            }
+        };
+
+        let difficulty = &self.difficulty;
+
+        for (name, value) in [
+            ("window_width", self.window_width),
+            ("window_height", self.window_height),
+            ("scrolling_speed", self.scrolling_speed),
+            ("floor_thickness", self.floor_thickness),
+            (
+                "difficulty.hardest_scrolling_speed_scale",
+                difficulty.hardest_scrolling_speed_scale,
+            ),
+            (
+                "difficulty.hardest_pipe_gap_scale",
+                difficulty.hardest_pipe_gap_scale,
+            ),
+            (
+                "difficulty.hardest_pipe_distance_scale",
+                difficulty.hardest_pipe_distance_scale,
+            ),
+        ] {
+            check(
+                value.is_finite() && value > 0.0,
+                format!("{} must be greater than 0, got {}", name, value),
+            );
+        }
+
+        check(
+            !difficulty.curve.is_empty(),
+            "difficulty.curve must have at least one point".to_string(),
+        );
+        for (score, level) in &difficulty.curve {
+            check(
+                (0.0..=1.0).contains(level),
+                format!(
+                    "difficulty.curve level at score {} must be between 0 and 1, got {}",
+                    score, level
+                ),
+            );
+        }
+        for points in difficulty.curve.windows(2) {
+            check(
+                points[0].0 < points[1].0,
+                format!(
+                    "difficulty.curve scores must be increasing, got {} then {}",
+                    points[0].0, points[1].0
+                ),
+            );
+        }
+
+        for preset in Preset::ALL {
+            let tuning = self.presets.get(preset);
+            let name =
+                |field: &str| format!("presets.{}.{}", preset.to_string().to_lowercase(), field);
+
+            for (field, value) in [
+                ("gravity", tuning.gravity),
+                ("flappy_jump_strength", tuning.flappy_jump_strength),
+                ("pipe_gap", tuning.pipe_gap),
+            ] {
+                check(
+                    value.is_finite() && value > 0.0,
+                    format!("{} must be greater than 0, got {}", name(field), value),
+                );
+            }
+
+            // along the whole difficulty curve
+            let pipe_distances = [
+                tuning.pipe_distance,
+                tuning.pipe_distance * difficulty.hardest_pipe_distance_scale,
+            ];
+            check(
+                pipe_distances
+                    .iter()
+                    .all(|distance| distance.is_finite() && *distance > PIPE_WIDTH),
+                format!(
+                    "{} must stay greater than the pipe width ({}), got {} ramping to {}",
+                    name("pipe_distance"),
+                    PIPE_WIDTH,
+                    pipe_distances[0],
+                    pipe_distances[1]
+                ),
+            );
+            check(
+                tuning.pipe_gap_min_y <= tuning.pipe_gap_max_y,
+                format!(
+                    "{} ({}) must not be greater than {} ({})",
+                    name("pipe_gap_min_y"),
+                    tuning.pipe_gap_min_y,
+                    name("pipe_gap_max_y"),
+                    tuning.pipe_gap_max_y
+                ),
+            );
+
+            // the whole gap must stay visible, between the floor and the window top,
+            // even at its widest along the difficulty curve
+            let pipe_gap = tuning
+                .pipe_gap
+                .max(tuning.pipe_gap * difficulty.hardest_pipe_gap_scale);
+            let lowest_gap_bottom = tuning.pipe_gap_min_y - pipe_gap / 2.0;
+            let floor_top = -self.window_height / 2.0 + self.floor_thickness;
+            check(
+                lowest_gap_bottom >= floor_top,
+                format!(
+                    "the lowest gap goes down to {} and is hidden by the floor, \
+                     which ends at {}: raise {} or reduce {}",
+                    lowest_gap_bottom,
+                    floor_top,
+                    name("pipe_gap_min_y"),
+                    name("pipe_gap")
+                ),
+            );
+            let highest_gap_top = tuning.pipe_gap_max_y + pipe_gap / 2.0;
+            let window_top = self.window_height / 2.0;
+            check(
+                highest_gap_top <= window_top,
+                format!(
+                    "the highest gap goes up to {} and is above the window top, \
+                     at {}: lower {} or reduce {}",
+                    highest_gap_top,
+                    window_top,
+                    name("pipe_gap_max_y"),
+                    name("pipe_gap")
+                ),
+            );
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+}
+
+/// Load the config before building the app, the window size depends on it.
+/// A broken config stops the game with the reason instead of silently
+/// playing with other values.
+pub fn load_or_exit() -> GameConfig {
+    GameConfig::load().unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    })
+}
+
+/// Hot reload of the config through the `AssetServer`, only added when rendering
+pub struct GameConfigPlugin;
+
+impl Plugin for GameConfigPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<GameConfig>();
+        app.init_asset_loader::<GameConfigLoader>();
+        app.add_startup_system(watch_config);
+        app.add_system(apply_config_changes);
+    }
+}
+
+#[derive(Default)]
+struct GameConfigLoader;
+
+impl AssetLoader for GameConfigLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            // the `AssetServer` reports an invalid edit, the previous config is kept
+            let config = GameConfig::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(config));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["config.ron"]
+    }
+}
+
+/// Keeps the watched config loaded
+struct GameConfigHandle(Handle<GameConfig>);
+
+//
+// -- SYSTEM
+//
+
+fn watch_config(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(GameConfigHandle(asset_server.load(GAME_CONFIG_PATH)));
+}
+
+fn apply_config_changes(
+    mut asset_events: EventReader<AssetEvent<GameConfig>>,
+    config_assets: Res<Assets<GameConfig>>,
+    config_handle: Res<GameConfigHandle>,
+    mut config: ResMut<GameConfig>,
+    mut windows: ResMut<Windows>,
+) {
+    for event in asset_events.iter() {
+        let (AssetEvent::Created { handle } | AssetEvent::Modified { handle }) = event else {
+            continue;
+        };
+        if *handle != config_handle.0 {
+            continue;
+        }
+        let Some(loaded_config) = config_assets.get(handle) else {
+            continue;
+        };
+        if *loaded_config == *config {
+            continue;
+        }
+
+        info!("{} reloaded", GAME_CONFIG_PATH);
+        *config = loaded_config.clone();
+
+        if let Some(window) = windows.get_primary_mut() {
+            window.set_resolution(config.window_width, config.window_height);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every problem found in `config`, none when valid
+    fn problems(config: &GameConfig) -> Vec<String> {
+        match config.validate() {
+            Ok(()) => Vec::new(),
+            Err(ConfigError::Invalid(problems)) => problems,
+            Err(err) => panic!("expected invalid values, got {}", err),
+        }
+    }
+
+    fn assert_problem(config: &GameConfig, expected: &str) {
+        let problems = problems(config);
+        assert!(
+            problems.iter().any(|problem| problem.contains(expected)),
+            "expected a problem with \"{}\", got {:?}",
+            expected,
+            problems
+        );
+    }
+
+    #[test]
+    fn shipped_config_is_valid() {
+        assert!(problems(&GameConfig::default()).is_empty());
+        GameConfig::from_bytes(include_bytes!("../assets/game.config.ron")).unwrap();
+    }
+
+    #[test]
+    fn missing_fields_keep_their_default_and_unknown_ones_are_rejected() {
+        assert_eq!(
+            GameConfig::from_bytes(b"(scrolling_speed: 200.0)").unwrap(),
+            GameConfig {
+                scrolling_speed: 200.0,
+                ..default()
+            }
+        );
+
+        for typo in [
+            &b"(scroling_speed: 200.0)"[..],
+            &b"(presets: (medium: ()))"[..],
+            &b"(difficulty: (curves: []))"[..],
+        ] {
+            assert!(matches!(
+                GameConfig::from_bytes(typo),
+                Err(ConfigError::Parse(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn values_must_be_positive() {
+        for (config, field) in [
+            (
+                GameConfig {
+                    scrolling_speed: 0.0,
+                    ..default()
+                },
+                "scrolling_speed",
+            ),
+            (
+                GameConfig {
+                    window_width: -500.0,
+                    ..default()
+                },
+                "window_width",
+            ),
+            (
+                GameConfig {
+                    difficulty: DifficultyCurve {
+                        hardest_pipe_gap_scale: f32::NAN,
+                        ..default()
+                    },
+                    ..default()
+                },
+                "difficulty.hardest_pipe_gap_scale",
+            ),
+        ] {
+            assert_problem(&config, &format!("{} must be greater than 0", field));
+        }
+
+        let mut config = GameConfig::default();
+        config.presets.hard.gravity = -1.0;
+        assert_problem(&config, "presets.hard.gravity must be greater than 0");
+    }
+
+    #[test]
+    fn difficulty_curve_must_rise_in_score_between_levels_0_and_1() {
+        let with_curve = |curve: Vec<(u32, f32)>| GameConfig {
+            difficulty: DifficultyCurve { curve, ..default() },
+            ..default()
+        };
+
+        assert_problem(&with_curve(vec![]), "at least one point");
+        assert_problem(
+            &with_curve(vec![(0, 0.0), (10, 0.5), (10, 0.6)]),
+            "scores must be increasing, got 10 then 10",
+        );
+        assert_problem(
+            &with_curve(vec![(10, 0.5), (0, 0.0)]),
+            "scores must be increasing, got 10 then 0",
+        );
+        assert_problem(
+            &with_curve(vec![(0, 0.0), (10, 1.5)]),
+            "level at score 10 must be between 0 and 1",
+        );
+        assert!(problems(&with_curve(vec![(5, 0.2)])).is_empty());
+    }
+
+    #[test]
+    fn pipe_sets_must_not_overlap() {
+        let mut config = GameConfig::default();
+        config.presets.easy.pipe_distance = PIPE_WIDTH;
+        assert_problem(&config, "presets.easy.pipe_distance must stay greater");
+
+        // only once the curve ramps it down
+        let mut config = GameConfig::default();
+        config.presets.easy.pipe_distance = PIPE_WIDTH * 1.1;
+        config.difficulty.hardest_pipe_distance_scale = 0.5;
+        assert_problem(&config, "presets.easy.pipe_distance must stay greater");
+    }
+
+    #[test]
+    fn gaps_must_stay_in_the_window() {
+        let mut config = GameConfig::default();
+        config.presets.normal.pipe_gap_min_y = 100.0;
+        config.presets.normal.pipe_gap_max_y = -100.0;
+        assert_problem(
+            &config,
+            "presets.normal.pipe_gap_min_y (100) must not be greater",
+        );
+
+        let mut config = GameConfig::default();
+        config.presets.normal.pipe_gap_min_y = -config.window_height / 2.0;
+        assert_problem(&config, "hidden by the floor");
+
+        let mut config = GameConfig::default();
+        config.presets.normal.pipe_gap_max_y = config.window_height / 2.0;
+        assert_problem(&config, "above the window top");
+    }
+}
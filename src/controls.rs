@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::save::SaveStorage;
+
+const CONTROLS_FILE_NAME: &str = "controls.ron";
+
+/// What the player wants to do, whatever device it comes from. Read it from
+/// `Res<Input<Action>>` instead of the raw device inputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Flap,
+    Confirm,
+    Pause,
+    Back,
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    /// Any finger on the touchscreen
+    Touch,
+    /// The button on any connected gamepad, including ones connected mid-game
+    Gamepad(GamepadButtonType),
+}
+
+/// Named the way the player would press it, e.g. "space" or "left click"
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "{}", format!("{:?}", key).to_lowercase()),
+            Binding::Mouse(MouseButton::Other(button)) => write!(f, "mouse button {}", button),
+            Binding::Mouse(button) => write!(f, "{} click", format!("{:?}", button).to_lowercase()),
+            Binding::Touch => write!(f, "the screen"),
+            Binding::Gamepad(button_type) => {
+                write!(f, "gamepad {}", format!("{:?}", button_type).to_lowercase())
+            }
+        }
+    }
+}
+
+/// Bindings of every action, loaded from `controls.ron` in the save directory,
+/// for example:
+///
+/// ```ron
+/// (
+///     bindings: {
+///         Flap: [Key(Space), Mouse(Left), Gamepad(South)],
+///     },
+/// )
+/// ```
+///
+/// Actions missing from the file keep their default bindings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionBindings {
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for ActionBindings {
+    fn default() -> Self {
+        use Binding::*;
+
+        ActionBindings {
+            bindings: HashMap::from([
+                (
+                    Action::Flap,
+                    vec![
+                        Key(KeyCode::Space),
+                        Mouse(MouseButton::Left),
+                        Touch,
+                        Gamepad(GamepadButtonType::South),
+                    ],
+                ),
+                (
+                    Action::Confirm,
+                    vec![
+                        Key(KeyCode::Space),
+                        Key(KeyCode::Return),
+                        Gamepad(GamepadButtonType::South),
+                    ],
+                ),
+                (
+                    Action::Pause,
+                    vec![
+                        Key(KeyCode::Escape),
+                        Key(KeyCode::P),
+                        Gamepad(GamepadButtonType::Start),
+                    ],
+                ),
+                (
+                    Action::Back,
+                    vec![
+                        Key(KeyCode::Escape),
+                        Key(KeyCode::Back),
+                        Gamepad(GamepadButtonType::East),
+                    ],
+                ),
+                (
+                    Action::Up,
+                    vec![
+                        Key(KeyCode::Up),
+                        Key(KeyCode::W),
+                        Gamepad(GamepadButtonType::DPadUp),
+                    ],
+                ),
+                (
+                    Action::Down,
+                    vec![
+                        Key(KeyCode::Down),
+                        Key(KeyCode::S),
+                        Gamepad(GamepadButtonType::DPadDown),
+                    ],
+                ),
+            ]),
+        }
+    }
+}
+
+impl ActionBindings {
+    /// The binding listed first for `action`, the one to show the player
+    pub fn first(&self, action: Action) -> Option<Binding> {
+        self.bindings.get(&action)?.first().copied()
+    }
+}
+
+/// Devices state read to update the actions
+struct Devices<'a> {
+    keyboard: &'a Input<KeyCode>,
+    mouse: &'a Input<MouseButton>,
+    touches: &'a Touches,
+    gamepads: &'a Gamepads,
+    gamepad_buttons: &'a Input<GamepadButton>,
+}
+
+impl Devices<'_> {
+    fn is_pressed(&self, binding: &Binding) -> bool {
+        match binding {
+            Binding::Key(key) => self.keyboard.pressed(*key),
+            Binding::Mouse(button) => self.mouse.pressed(*button),
+            Binding::Touch => self.touches.iter().next().is_some(),
+            Binding::Gamepad(button_type) => self.gamepads.iter().any(|gamepad| {
+                self.gamepad_buttons
+                    .pressed(GamepadButton::new(*gamepad, *button_type))
+            }),
+        }
+    }
+
+    fn is_just_pressed(&self, binding: &Binding) -> bool {
+        match binding {
+            Binding::Key(key) => self.keyboard.just_pressed(*key),
+            Binding::Mouse(button) => self.mouse.just_pressed(*button),
+            Binding::Touch => self.touches.any_just_pressed(),
+            Binding::Gamepad(button_type) => self.gamepads.iter().any(|gamepad| {
+                self.gamepad_buttons
+                    .just_pressed(GamepadButton::new(*gamepad, *button_type))
+            }),
+        }
+    }
+}
+
+pub struct ControlsPlugin;
+
+/// Updates `Input<Action>`, runs in `CoreStage::PreUpdate` after the device inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+pub struct ActionSystem;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Input<Action>>();
+        app.init_resource::<ActionBindings>();
+        app.init_resource::<SaveStorage>();
+        app.add_startup_system(load_bindings);
+        app.add_system_to_stage(
+            CoreStage::PreUpdate,
+            update_actions.label(ActionSystem).after(InputSystem),
+        );
+        app.add_system(log_gamepad_connections);
+    }
+}
+
+fn load_bindings(storage: Res<SaveStorage>, mut action_bindings: ResMut<ActionBindings>) {
+    let loaded_bindings: ActionBindings = storage.load(CONTROLS_FILE_NAME);
+
+    action_bindings.bindings.extend(loaded_bindings.bindings);
+}
+
+fn update_actions(
+    mut actions: ResMut<Input<Action>>,
+    action_bindings: Res<ActionBindings>,
+    keyboard: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    touches: Res<Touches>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+) {
+    actions.clear();
+
+    let devices = Devices {
+        keyboard: &keyboard,
+        mouse: &mouse,
+        touches: &touches,
+        gamepads: &gamepads,
+        gamepad_buttons: &gamepad_buttons,
+    };
+
+    for (action, bindings) in &action_bindings.bindings {
+        let is_pressed = bindings.iter().any(|binding| devices.is_pressed(binding));
+        let is_just_pressed = bindings
+            .iter()
+            .any(|binding| devices.is_just_pressed(binding));
+
+        if is_just_pressed {
+            // a new press while another device holds the action, e.g. a second finger
+            actions.release(*action);
+            actions.press(*action);
+        } else if is_pressed && !actions.pressed(*action) {
+            actions.press(*action);
+        } else if !is_pressed && actions.pressed(*action) {
+            actions.release(*action);
+        }
+    }
+}
+
+fn log_gamepad_connections(mut gamepad_events: EventReader<GamepadEvent>) {
+    for GamepadEvent {
+        gamepad,
+        event_type,
+    } in gamepad_events.iter()
+    {
+        match event_type {
+            GamepadEventType::Connected => info!("{:?} connected", gamepad),
+            GamepadEventType::Disconnected => info!("{:?} disconnected", gamepad),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use std::fmt;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::cli;
+use crate::config::GameConfig;
+use crate::game_state::GameState;
+use crate::physics::{PhysicsStage, StorePreviousTranslationSystem};
+use crate::score::Scoreboard;
+
+/// Difficulty chosen by the player for the next runs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Preset {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 3] = [Preset::Easy, Preset::Normal, Preset::Hard];
+
+    /// `--preset easy|normal|hard`
+    pub fn from_args() -> Self {
+        let Some(name) = cli::arg_value("--preset") else {
+            return Preset::default();
+        };
+
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.to_string().eq_ignore_ascii_case(&name))
+            .expect("--preset must be easy, normal or hard")
+    }
+
+    /// The following preset, back to the easiest after the hardest
+    pub fn next(self) -> Self {
+        match self {
+            Preset::Easy => Preset::Normal,
+            Preset::Normal => Preset::Hard,
+            Preset::Hard => Preset::Easy,
+        }
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Preset::Easy => "Easy",
+            Preset::Normal => "Normal",
+            Preset::Hard => "Hard",
+        };
+
+        f.write_str(name)
+    }
+}
+
+/// Physics and course values of a preset, at the start of a run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PresetTuning {
+    /// Downward acceleration of flappy, in pixels per second squared
+    pub gravity: f32,
+    /// Upward velocity given by a flap, in pixels per second
+    pub flappy_jump_strength: f32,
+    /// Height of the opening between two pipes
+    pub pipe_gap: f32,
+    /// Horizontal distance between two pipe sets
+    pub pipe_distance: f32,
+    /// Lowest center of the opening between two pipes
+    pub pipe_gap_min_y: f32,
+    /// Highest center of the opening between two pipes
+    pub pipe_gap_max_y: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PresetTunings {
+    pub easy: PresetTuning,
+    pub normal: PresetTuning,
+    pub hard: PresetTuning,
+}
+
+impl Default for PresetTunings {
+    fn default() -> Self {
+        PresetTunings {
+            easy: PresetTuning {
+                gravity: 2000.0,
+                flappy_jump_strength: 650.0,
+                pipe_gap: 240.0,
+                pipe_distance: 400.0,
+                pipe_gap_min_y: -150.0,
+                pipe_gap_max_y: 150.0,
+            },
+            normal: PresetTuning {
+                gravity: 2400.0,
+                flappy_jump_strength: 700.0,
+                pipe_gap: 200.0,
+                pipe_distance: 350.0,
+                pipe_gap_min_y: -200.0,
+                pipe_gap_max_y: 200.0,
+            },
+            hard: PresetTuning {
+                gravity: 2700.0,
+                flappy_jump_strength: 750.0,
+                pipe_gap: 175.0,
+                pipe_distance: 320.0,
+                pipe_gap_min_y: -220.0,
+                pipe_gap_max_y: 220.0,
+            },
+        }
+    }
+}
+
+impl PresetTunings {
+    pub fn get(&self, preset: Preset) -> &PresetTuning {
+        match preset {
+            Preset::Easy => &self.easy,
+            Preset::Normal => &self.normal,
+            Preset::Hard => &self.hard,
+        }
+    }
+}
+
+/// How the course gets harder as the score rises. The level goes from 0, the
+/// values of the preset, to 1, where they are multiplied by the hardest scales.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DifficultyCurve {
+    /// `(score, level)` points, sorted by score. The level is interpolated
+    /// between two points, and kept before the first and after the last one.
+    pub curve: Vec<(u32, f32)>,
+    pub hardest_scrolling_speed_scale: f32,
+    pub hardest_pipe_gap_scale: f32,
+    pub hardest_pipe_distance_scale: f32,
+}
+
+impl Default for DifficultyCurve {
+    fn default() -> Self {
+        DifficultyCurve {
+            curve: vec![(0, 0.0), (10, 0.3), (50, 1.0)],
+            hardest_scrolling_speed_scale: 1.5,
+            hardest_pipe_gap_scale: 0.8,
+            hardest_pipe_distance_scale: 0.85,
+        }
+    }
+}
+
+impl DifficultyCurve {
+    fn level_at(&self, score: u32) -> f32 {
+        let next_point_index = self
+            .curve
+            .partition_point(|(point_score, _)| *point_score <= score);
+
+        match (
+            next_point_index
+                .checked_sub(1)
+                .and_then(|index| self.curve.get(index)),
+            self.curve.get(next_point_index),
+        ) {
+            (Some((previous_score, previous_level)), Some((next_score, next_level))) => {
+                let progress =
+                    (score - previous_score) as f32 / (next_score - previous_score) as f32;
+                previous_level + (next_level - previous_level) * progress
+            }
+            (Some((_, level)), None) | (None, Some((_, level))) => *level,
+            (None, None) => 0.0,
+        }
+    }
+}
+
+/// Gameplay values of the current run, from its preset ramped up with the
+/// score. Read them instead of the `GameConfig` ones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difficulty {
+    pub gravity: f32,
+    pub flappy_jump_strength: f32,
+    pub scrolling_speed: f32,
+    pub pipe_gap: f32,
+    pub pipe_distance: f32,
+    pub pipe_gap_min_y: f32,
+    pub pipe_gap_max_y: f32,
+}
+
+impl Difficulty {
+    pub fn at_score(config: &GameConfig, preset: Preset, score: u32) -> Self {
+        let tuning = config.presets.get(preset);
+        let curve = &config.difficulty;
+        let level = curve.level_at(score);
+        let ramp = |base: f32, hardest_scale: f32| base * (1.0 + (hardest_scale - 1.0) * level);
+
+        Difficulty {
+            gravity: tuning.gravity,
+            flappy_jump_strength: tuning.flappy_jump_strength,
+            scrolling_speed: ramp(config.scrolling_speed, curve.hardest_scrolling_speed_scale),
+            pipe_gap: ramp(tuning.pipe_gap, curve.hardest_pipe_gap_scale),
+            pipe_distance: ramp(tuning.pipe_distance, curve.hardest_pipe_distance_scale),
+            pipe_gap_min_y: tuning.pipe_gap_min_y,
+            pipe_gap_max_y: tuning.pipe_gap_max_y,
+        }
+    }
+
+    /// Difficulty of a new run
+    pub fn starting(config: &GameConfig, preset: Preset) -> Self {
+        Self::at_score(config, preset, 0)
+    }
+}
+
+impl FromWorld for Difficulty {
+    fn from_world(world: &mut World) -> Self {
+        Difficulty::starting(world.resource::<GameConfig>(), *world.resource::<Preset>())
+    }
+}
+
+pub struct DifficultyPlugin;
+
+impl Plugin for DifficultyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Preset>();
+        app.init_resource::<Difficulty>();
+        // first thing of a physics step, so every system of the step sees the same values
+        app.add_system_to_stage(
+            PhysicsStage,
+            update_difficulty.before(StorePreviousTranslationSystem),
+        );
+        app.add_system_set(SystemSet::on_enter(GameState::Playing).with_system(reset_difficulty));
+    }
+}
+
+//
+// -- SYSTEM
+//
+
+fn reset_difficulty(
+    config: Res<GameConfig>,
+    preset: Res<Preset>,
+    mut difficulty: ResMut<Difficulty>,
+) {
+    *difficulty = Difficulty::starting(&config, *preset);
+}
+
+fn update_difficulty(
+    config: Res<GameConfig>,
+    scoreboard: Res<Scoreboard>,
+    mut difficulty: ResMut<Difficulty>,
+) {
+    if !config.is_changed() && !scoreboard.is_changed() {
+        return;
+    }
+
+    let new_difficulty =
+        Difficulty::at_score(&config, scoreboard.preset(), scoreboard.current_score());
+    if *difficulty != new_difficulty {
+        *difficulty = new_difficulty;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_level(curve: &DifficultyCurve, score: u32, level: f32) {
+        assert!(
+            (curve.level_at(score) - level).abs() < 1e-6,
+            "level at score {} is {}, expected {}",
+            score,
+            curve.level_at(score),
+            level
+        );
+    }
+
+    #[test]
+    fn level_follows_the_curve_points() {
+        // (0, 0.0), (10, 0.3), (50, 1.0)
+        let curve = DifficultyCurve::default();
+
+        assert_level(&curve, 0, 0.0);
+        assert_level(&curve, 5, 0.15);
+        assert_level(&curve, 10, 0.3);
+        assert_level(&curve, 11, 0.3 + 0.7 / 40.0);
+        assert_level(&curve, 49, 1.0 - 0.7 / 40.0);
+        assert_level(&curve, 50, 1.0);
+        assert_level(&curve, 51, 1.0);
+        assert_level(&curve, u32::MAX, 1.0);
+    }
+
+    #[test]
+    fn level_is_kept_outside_the_curve() {
+        let curve = DifficultyCurve {
+            curve: vec![(5, 0.2), (10, 0.4)],
+            ..default()
+        };
+        assert_level(&curve, 0, 0.2);
+        assert_level(&curve, 5, 0.2);
+        assert_level(&curve, 10, 0.4);
+        assert_level(&curve, 100, 0.4);
+
+        let single_point = DifficultyCurve {
+            curve: vec![(3, 0.5)],
+            ..default()
+        };
+        assert_level(&single_point, 0, 0.5);
+        assert_level(&single_point, 3, 0.5);
+        assert_level(&single_point, 4, 0.5);
+    }
+
+    #[test]
+    fn difficulty_ramps_from_the_preset_to_the_hardest_scales() {
+        let config = GameConfig::default();
+        let tuning = config.presets.get(Preset::Hard);
+        let curve = &config.difficulty;
+
+        let starting = Difficulty::starting(&config, Preset::Hard);
+        assert_eq!(starting, Difficulty::at_score(&config, Preset::Hard, 0));
+        assert_eq!(starting.scrolling_speed, config.scrolling_speed);
+        assert_eq!(starting.pipe_gap, tuning.pipe_gap);
+        assert_eq!(starting.pipe_distance, tuning.pipe_distance);
+
+        let hardest = Difficulty::at_score(&config, Preset::Hard, 50);
+        assert_eq!(
+            hardest.scrolling_speed,
+            config.scrolling_speed * curve.hardest_scrolling_speed_scale
+        );
+        assert_eq!(
+            hardest.pipe_gap,
+            tuning.pipe_gap * curve.hardest_pipe_gap_scale
+        );
+        assert_eq!(
+            hardest.pipe_distance,
+            tuning.pipe_distance * curve.hardest_pipe_distance_scale
+        );
+        assert_eq!(hardest.gravity, tuning.gravity);
+        assert_eq!(Difficulty::at_score(&config, Preset::Hard, 500), hardest);
+
+        // strictly between, on the way up
+        let halfway = Difficulty::at_score(&config, Preset::Hard, 10);
+        assert!(halfway.scrolling_speed > starting.scrolling_speed);
+        assert!(halfway.scrolling_speed < hardest.scrolling_speed);
+        assert!(halfway.pipe_gap < starting.pipe_gap);
+        assert!(halfway.pipe_gap > hardest.pipe_gap);
+    }
+}
@@ -1,81 +1,167 @@
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_6, TAU};
+
 use bevy::prelude::*;
-use bevy::sprite::collide_aabb;
-
-use crate::animation::{Animation, AnimationReplayEvent};
-use crate::collider::Collider;
-use crate::game_state::GameState;
-use crate::gravity::GravityAffected;
-use crate::pipe::PipeGap;
-use crate::score::Scoreboard;
+
+use crate::animation::{Animation, AnimationCommand, AnimationEvents, AnimationFinished};
+use crate::collider::{Collider, CollisionLayers};
+use crate::collision::{CollisionStarted, CollisionSystem};
+use crate::config::GameConfig;
+use crate::controls::Action;
+use crate::difficulty::{Difficulty, Preset};
+use crate::game_state::{unless_paused, while_playing, GameState};
+use crate::ghost::Ghost;
+use crate::gravity::{ApplyGravitySystem, GravityAffected};
+use crate::physics::{
+    PhysicsStage, PreviousTranslation, StorePreviousTranslationSystem, PHYSICS_DELTA_SECONDS,
+};
 use crate::velocity::{ApplyVelocitySystem, Velocity};
-use crate::{window::*, SCROLLING_SPEED};
+use crate::window::*;
+use crate::FLAPPY_STARTING_POSITION;
+
+const FLAPPY_ANIMATIONS_PATH: &str = "characters.anim.ron";
+pub const IDLE_CLIP: &str = "idle";
+pub const FLAP_CLIP: &str = "flap";
+pub const DEAD_CLIP: &str = "dead";
+// Frame event of the wings beating down
+const WING_DOWN_EVENT: &str = "wing_down";
 
 const FLAPPY_SPRITE_SIZE: f32 = 24.0;
-const FLAPPY_SPRITE_SCALE: Vec3 = Vec3::splat(2.0);
+pub const FLAPPY_SPRITE_SCALE: Vec3 = Vec3::splat(2.0);
 const FLAPPY_SIZE: Vec3 = Vec3::new(
     FLAPPY_SPRITE_SCALE.x * FLAPPY_SPRITE_SIZE,
     FLAPPY_SPRITE_SCALE.y * FLAPPY_SPRITE_SIZE,
     0.0,
 );
-const FLAPPY_COLLISION_SIZE: Vec3 = Vec3::new(FLAPPY_SIZE.x * 0.65, FLAPPY_SIZE.y * 0.65, 0.0);
-const FLAPPY_JUMP_STRENGTH: f32 = 700.0;
-// Max height flappy can jump above the window height
-const FLAPPY_MAX_FLY_HEIGHT: f32 = (WINDOW_HEIGHT / 2.0) + WINDOW_BOUND_LIMIT;
+// Round body from the tail to the beak, in sprite pixels, scaled and turned
+// with the sprite
+const FLAPPY_COLLIDER_LENGTH: f32 = FLAPPY_SPRITE_SIZE * 0.15;
+const FLAPPY_COLLIDER_RADIUS: f32 = FLAPPY_SPRITE_SIZE * 0.27;
+
+// Tilt in radians for a vertical velocity of 1 pixel per second
+const FLAPPY_TILT_PER_VELOCITY: f32 = 0.0015;
+// Nose up after a flap, nose down when falling fast
+const FLAPPY_MAX_TILT: f32 = FRAC_PI_6;
+const FLAPPY_MIN_TILT: f32 = -FRAC_PI_2;
+// How fast the tilt catches up with the velocity, per second
+const FLAPPY_TILT_SMOOTHING: f32 = 12.0;
+// Height and frequency of the bobbing while waiting for the first run
+const FLAPPY_HOVER_AMPLITUDE: f32 = 12.0;
+const FLAPPY_HOVER_FREQUENCY: f32 = 1.2;
+// Upward velocity of the hop flappy makes when it dies
+const FLAPPY_DEATH_HOP: f32 = 400.0;
+// Radians per second, clockwise
+const FLAPPY_DEATH_SPIN_SPEED: f32 = TAU * 1.5;
+
+const DUST_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.6);
+const DUST_SIZE: f32 = 8.0;
+const DUST_GROWTH: f32 = 2.0;
+const DUST_LIFETIME_SECONDS: f32 = 0.3;
 
 #[derive(Component)]
 pub struct Flappy;
 
+/// Bobbing up and down in place until the first run starts
+#[derive(Component)]
+pub struct Hover {
+    center_y: f32,
+    elapsed_seconds: f32,
+}
+
+/// Puff left behind by a wing beat, fading out
 #[derive(Component)]
-pub struct FlappyCollider {
-    pub enabled: bool,
+struct Dust {
+    timer: Timer,
 }
 
+/// Spinning down once the `DEAD_CLIP` is over
+#[derive(Component)]
+struct DeathSpin;
+
+/// Flap asked for since the last physics step, applied by the next one
+#[derive(Default)]
+pub struct FlapRequest(pub bool);
+
 pub struct FlappyPlugin;
 
+/// Applies the `FlapRequest` at the start of a physics step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+pub struct FlapSystem;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+struct FlappyLimitMovementSystem;
+
 impl Plugin for FlappyPlugin {
     fn build(&self, app: &mut App) {
-        use bevy::transform::TransformSystem;
-
+        app.init_resource::<FlapRequest>();
         app.add_system_set(
             SystemSet::on_enter(GameState::Playing).with_system(flappy_setup_playing),
         );
-        app.add_system_set(
-            SystemSet::on_update(GameState::Playing)
-                .with_system(flappy_jump)
-                .with_system(flappy_limit_movement.after(ApplyVelocitySystem)),
-        );
+        app.add_system_set(SystemSet::on_update(GameState::Playing).with_system(flappy_jump));
         app.add_system_set_to_stage(
-            CoreStage::PostUpdate,
+            PhysicsStage,
             SystemSet::new()
-                .with_system(check_for_collision)
-                .after(TransformSystem::TransformPropagate),
+                .with_run_criteria(while_playing)
+                .with_system(
+                    flappy_flap
+                        .label(FlapSystem)
+                        .after(StorePreviousTranslationSystem)
+                        .before(ApplyGravitySystem),
+                )
+                .with_system(
+                    flappy_keep_pace
+                        .after(StorePreviousTranslationSystem)
+                        .before(ApplyVelocitySystem),
+                )
+                .with_system(
+                    flappy_tilt
+                        .after(ApplyGravitySystem)
+                        .before(FlappyLimitMovementSystem),
+                )
+                .with_system(flappy_crash.after(CollisionSystem))
+                .with_system(flappy_impact_dust.after(CollisionSystem)),
+        );
+        app.add_system_to_stage(
+            PhysicsStage,
+            flappy_hover
+                .with_run_criteria(unless_paused)
+                .after(ApplyVelocitySystem)
+                .before(FlappyLimitMovementSystem),
+        );
+        app.add_system_to_stage(
+            PhysicsStage,
+            flappy_limit_movement
+                .with_run_criteria(unless_paused)
+                .label(FlappyLimitMovementSystem)
+                .after(ApplyVelocitySystem)
+                .before(CollisionSystem),
         );
         app.add_system_set(
             SystemSet::on_enter(GameState::GameOver).with_system(flappy_forward_stop),
         );
+        app.add_system(flappy_wing_dust);
+        app.add_system(fade_dust.with_run_criteria(unless_paused));
+        app.add_system_set(
+            SystemSet::on_update(GameState::GameOver)
+                .with_system(flappy_start_death_spin)
+                .with_system(flappy_death_spin),
+        );
     }
 }
 
-pub fn spawn(
-    commands: &mut Commands,
-    asset_server: Res<AssetServer>,
-    mut texture_atlas_map: ResMut<Assets<TextureAtlas>>,
-    position: Vec3,
-) {
-    let texture_handle = asset_server.load("characters.png");
-    let texture_atlas =
-        TextureAtlas::from_grid(texture_handle, Vec2::splat(FLAPPY_SPRITE_SIZE), 9, 3);
-    let texture_atlas_handle = texture_atlas_map.add(texture_atlas);
-
+pub fn spawn(commands: &mut Commands, asset_server: &AssetServer, position: Vec3) {
     commands
         .spawn()
         .insert(Name::new("Flappy"))
         .insert(Flappy)
         .insert(Velocity(Vec2::ZERO))
+        .insert(PreviousTranslation(position))
         .insert(GravityAffected(false))
-        .insert(FlappyCollider { enabled: true })
+        .insert(collider())
+        .insert(Hover {
+            center_y: position.y,
+            elapsed_seconds: 0.0,
+        })
         .insert_bundle(SpriteSheetBundle {
-            texture_atlas: texture_atlas_handle,
             transform: Transform {
                 translation: position,
                 scale: FLAPPY_SPRITE_SCALE,
@@ -87,87 +173,358 @@ pub fn spawn(
             },
             ..default()
         })
-        .insert(Animation {
-            timer: Timer::from_seconds(0.3, true),
-            frames: vec![25, 26, 24],
-            current_frame: 0,
-        });
+        .insert(animation(asset_server, IDLE_CLIP));
+}
+
+pub fn collider() -> Collider {
+    Collider::capsule(FLAPPY_COLLIDER_LENGTH, FLAPPY_COLLIDER_RADIUS)
+        .with_layers(CollisionLayers::PLAYER)
+        .with_mask(
+            CollisionLayers::OBSTACLE
+                | CollisionLayers::GROUND
+                | CollisionLayers::SCORE_TRIGGER
+                | CollisionLayers::PICKUP,
+        )
+}
+
+/// Animation of the flappy sprite sheet, starting with `clip`
+pub fn animation(asset_server: &AssetServer, clip: &str) -> Animation {
+    Animation::new(asset_server.load(FLAPPY_ANIMATIONS_PATH), clip)
 }
 
 //
 // -- System
 //
 
+/// Start a run from the starting position, or from where the hover is for the
+/// first one, keeping its vertical velocity
 fn flappy_setup_playing(
-    mut query: Query<(&mut Velocity, &mut GravityAffected, &mut Animation), With<Flappy>>,
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    preset: Res<Preset>,
+    mut flap_request: ResMut<FlapRequest>,
+    mut query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut PreviousTranslation,
+            &mut Velocity,
+            &mut GravityAffected,
+            &mut Collider,
+            &mut Animation,
+            Option<&Hover>,
+        ),
+        With<Flappy>,
+    >,
 ) {
-    let (mut velocity, mut gravity_affected, mut animation) = query.single_mut();
+    let (
+        flappy_entity,
+        mut transform,
+        mut previous_translation,
+        mut velocity,
+        mut gravity_affected,
+        mut collider,
+        mut animation,
+        hover,
+    ) = query.single_mut();
 
-    velocity.0 = Vec2::new(SCROLLING_SPEED, 0.0);
+    commands.entity(flappy_entity).remove::<DeathSpin>();
+    if hover.is_some() {
+        commands.entity(flappy_entity).remove::<Hover>();
+    } else {
+        transform.translation = FLAPPY_STARTING_POSITION;
+        previous_translation.0 = FLAPPY_STARTING_POSITION;
+        velocity.y = 0.0;
+    }
+    transform.rotation = Quat::default();
+    velocity.x = Difficulty::starting(&config, *preset).scrolling_speed;
     gravity_affected.0 = true;
-    animation.timer.set_repeating(false);
+    collider.enabled = true;
+    animation.play(FLAP_CLIP);
+    // a flap asked for at the end of the last run
+    flap_request.0 = false;
+}
+
+/// Fly as fast as the camera scrolls while the difficulty ramps up, beating
+/// the wings faster along
+fn flappy_keep_pace(
+    mut animation_commands: EventWriter<AnimationCommand>,
+    config: Res<GameConfig>,
+    difficulty: Res<Difficulty>,
+    mut query: Query<(Entity, &mut Velocity), With<Flappy>>,
+) {
+    if !difficulty.is_changed() {
+        return;
+    }
+
+    let (flappy_entity, mut velocity) = query.single_mut();
+    velocity.x = difficulty.scrolling_speed;
+    animation_commands.send(AnimationCommand::SetSpeed(
+        flappy_entity,
+        wing_speed(&config, &difficulty),
+    ));
+}
+
+/// Speed of the animations of a flappy flying at the pace of `difficulty`
+pub fn wing_speed(config: &GameConfig, difficulty: &Difficulty) -> f32 {
+    difficulty.scrolling_speed / config.scrolling_speed
+}
+
+fn flappy_jump(actions: Res<Input<Action>>, mut flap_request: ResMut<FlapRequest>) {
+    if actions.just_pressed(Action::Flap) {
+        flap_request.0 = true;
+    }
 }
 
-fn flappy_jump(
-    mut replay_event: EventWriter<AnimationReplayEvent>,
-    keyboard_input: Res<Input<KeyCode>>,
+fn flappy_flap(
+    mut animation_commands: EventWriter<AnimationCommand>,
+    difficulty: Res<Difficulty>,
+    mut flap_request: ResMut<FlapRequest>,
     mut query: Query<(Entity, &mut Velocity), With<Flappy>>,
 ) {
+    if !std::mem::take(&mut flap_request.0) {
+        return;
+    }
+
     let (flappy_entity, mut flappy_velocity) = query.single_mut();
+    flappy_velocity.y = difficulty.flappy_jump_strength;
+    animation_commands.send(AnimationCommand::Replay(flappy_entity));
+}
+
+fn flappy_hover(mut query: Query<(&mut Transform, &mut Velocity, &mut Hover)>) {
+    for (mut transform, mut velocity, mut hover) in &mut query {
+        hover.elapsed_seconds += PHYSICS_DELTA_SECONDS;
+        let phase = TAU * FLAPPY_HOVER_FREQUENCY * hover.elapsed_seconds;
+
+        // the velocity is what the physics carries on with when the run starts
+        transform.translation.y = hover.center_y + FLAPPY_HOVER_AMPLITUDE * phase.sin();
+        velocity.y = FLAPPY_HOVER_AMPLITUDE * TAU * FLAPPY_HOVER_FREQUENCY * phase.cos();
+    }
+}
+
+fn flappy_wing_dust(
+    mut commands: Commands,
+    mut animation_events: AnimationEvents,
+    query: Query<&Transform, With<Flappy>>,
+) {
+    for flappy_transform in animation_events.read_from(&query, WING_DOWN_EVENT) {
+        // under flappy, behind it
+        spawn_dust(
+            &mut commands,
+            flappy_transform.translation - Vec3::new(0.0, FLAPPY_SIZE.y / 2.0, 0.1),
+        );
+    }
+}
 
-    if keyboard_input.just_pressed(KeyCode::Space) {
-        flappy_velocity.y = FLAPPY_JUMP_STRENGTH;
-        replay_event.send(AnimationReplayEvent(flappy_entity));
+fn spawn_dust(commands: &mut Commands, translation: Vec3) {
+    commands
+        .spawn()
+        .insert(Name::new("Dust"))
+        .insert(Dust {
+            timer: Timer::from_seconds(DUST_LIFETIME_SECONDS, false),
+        })
+        .insert_bundle(SpriteBundle {
+            transform: Transform {
+                translation,
+                scale: Vec3::splat(DUST_SIZE),
+                ..default()
+            },
+            sprite: Sprite {
+                color: DUST_COLOR,
+                ..default()
+            },
+            ..default()
+        });
+}
+
+fn fade_dust(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Dust, &mut Sprite, &mut Transform)>,
+) {
+    for (dust_entity, mut dust, mut sprite, mut transform) in &mut query {
+        dust.timer.tick(time.delta());
+        if dust.timer.finished() {
+            commands.entity(dust_entity).despawn();
+            continue;
+        }
+
+        let progress = dust.timer.percent();
+        sprite.color.set_a(DUST_COLOR.a() * (1.0 - progress));
+        transform.scale = Vec3::splat(DUST_SIZE * (1.0 + (DUST_GROWTH - 1.0) * progress));
     }
 }
 
-fn flappy_limit_movement(mut query: Query<&mut Transform, With<Flappy>>) {
-    let mut flappy_transform = query.single_mut();
+/// Also limits the ghost, so it flies exactly like the recorded flappy
+fn flappy_limit_movement(
+    config: Res<GameConfig>,
+    mut query: Query<&mut Transform, Or<(With<Flappy>, With<Ghost>)>>,
+) {
+    // Max height flappy can jump above the window height
+    let max_fly_height = (config.window_height / 2.0) + WINDOW_BOUND_LIMIT;
 
-    if flappy_transform.translation.y > FLAPPY_MAX_FLY_HEIGHT {
-        flappy_transform.translation.y = FLAPPY_MAX_FLY_HEIGHT;
+    for mut flappy_transform in &mut query {
+        if flappy_transform.translation.y > max_fly_height {
+            flappy_transform.translation.y = max_fly_height;
+        }
     }
 }
 
-fn flappy_forward_stop(mut query: Query<(&mut Velocity, &mut FlappyCollider), With<Flappy>>) {
-    let (mut flappy_velocity, mut flappy_collider) = query.single_mut();
+/// Nose up when climbing and down when falling, easing toward the angle of the
+/// velocity. The ghost tilts along.
+fn flappy_tilt(mut query: Query<(&mut Transform, &Velocity), Or<(With<Flappy>, With<Ghost>)>>) {
+    let smoothing = 1.0 - (-FLAPPY_TILT_SMOOTHING * PHYSICS_DELTA_SECONDS).exp();
+
+    for (mut flappy_transform, flappy_velocity) in &mut query {
+        let (_, _, tilt) = flappy_transform.rotation.to_euler(EulerRot::XYZ);
+        let target_tilt =
+            (flappy_velocity.y * FLAPPY_TILT_PER_VELOCITY).clamp(FLAPPY_MIN_TILT, FLAPPY_MAX_TILT);
+
+        flappy_transform.rotation = Quat::from_rotation_z(tilt + (target_tilt - tilt) * smoothing);
+    }
+}
+
+/// Hop, then fall out of the window while spinning
+fn flappy_forward_stop(
+    mut query: Query<(&mut Velocity, &mut Collider, &mut Animation), With<Flappy>>,
+) {
+    let (mut flappy_velocity, mut flappy_collider, mut animation) = query.single_mut();
 
     flappy_velocity.x = 0.0;
+    flappy_velocity.y = FLAPPY_DEATH_HOP;
     flappy_collider.enabled = false;
+    animation.play(DEAD_CLIP);
 }
 
-fn check_for_collision(
+fn flappy_start_death_spin(
     mut commands: Commands,
-    mut scoreboard: ResMut<Scoreboard>,
+    mut finished_events: EventReader<AnimationFinished>,
+    query: Query<Entity, With<Flappy>>,
+) {
+    for finished_event in finished_events.iter() {
+        if finished_event.clip == DEAD_CLIP && query.contains(finished_event.entity) {
+            commands.entity(finished_event.entity).insert(DeathSpin);
+        }
+    }
+}
+
+fn flappy_death_spin(time: Res<Time>, mut query: Query<&mut Transform, With<DeathSpin>>) {
+    for mut flappy_transform in &mut query {
+        flappy_transform.rotate_z(-FLAPPY_DEATH_SPIN_SPEED * time.delta_seconds());
+    }
+}
+
+/// Hitting a pipe or the floor ends the run, flappy stops where it hit it
+pub fn flappy_crash(
+    mut collision_events: EventReader<CollisionStarted>,
     mut run_state: ResMut<State<GameState>>,
-    flappy_query: Query<(&Transform, &FlappyCollider), With<Flappy>>,
-    collider_query: Query<(Entity, &GlobalTransform, &Collider, Option<&PipeGap>)>,
+    mut query: Query<&mut Collider, With<Flappy>>,
+) {
+    for event in collision_events.iter() {
+        let Some((flappy_entity, _)) = event.between(
+            CollisionLayers::PLAYER,
+            CollisionLayers::OBSTACLE | CollisionLayers::GROUND,
+        ) else {
+            continue;
+        };
+        let Ok(mut collider) = query.get_mut(flappy_entity) else {
+            continue;
+        };
+        if !collider.enabled {
+            continue;
+        }
+
+        run_state.set(GameState::GameOver).unwrap();
+        // the next physics steps of this frame run before the game over
+        collider.enabled = false;
+    }
+}
+
+fn flappy_impact_dust(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionStarted>,
+    query: Query<&Transform, With<Flappy>>,
 ) {
-    let (flappy_transform, flappy_collider) = flappy_query.single();
-
-    for (collider_entity, collider_transform, collider, maybe_pipe_gap) in &collider_query {
-        let collider_translation = collider_transform.translation();
-        let collider_relative_position = collider.position();
-        let collider_position = Vec3::new(
-            collider_translation.x + collider_relative_position.x,
-            collider_translation.y + collider_relative_position.y,
-            collider_translation.z + collider_relative_position.z,
+    for event in collision_events.iter() {
+        let Some((flappy_entity, _)) = event.between(
+            CollisionLayers::PLAYER,
+            CollisionLayers::OBSTACLE | CollisionLayers::GROUND,
+        ) else {
+            continue;
+        };
+        let Ok(flappy_transform) = query.get(flappy_entity) else {
+            continue;
+        };
+
+        // in front of what was hit
+        let impact = event.contact.point + event.contact.normal * DUST_SIZE / 2.0;
+        spawn_dust(
+            &mut commands,
+            impact.extend(flappy_transform.translation.z + 0.1),
         );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Flappy playing, with the collisions of a step sent
+    fn crash_after(collisions: impl Fn(Entity) -> Vec<CollisionStarted>) -> (World, Entity) {
+        let mut world = World::new();
+        world.insert_resource(State::new(GameState::Playing));
+        world.init_resource::<Events<CollisionStarted>>();
+        let flappy = world.spawn().insert(Flappy).insert(collider()).id();
+        let mut state_stage =
+            SystemStage::single_threaded().with_system_set(State::<GameState>::get_driver());
+        // entered
+        state_stage.run(&mut world);
+
+        world
+            .resource_mut::<Events<CollisionStarted>>()
+            .extend(collisions(flappy));
+        SystemStage::single_threaded()
+            .with_system(flappy_crash)
+            .run(&mut world);
+        state_stage.run(&mut world);
 
-        let collision = collide_aabb::collide(
-            flappy_transform.translation,
-            FLAPPY_COLLISION_SIZE.truncate(),
-            collider_position,
-            *collider.scale(),
+        (world, flappy)
+    }
+
+    #[test]
+    fn crash_ends_the_run_once() {
+        // a pipe and the floor in the same step
+        let (world, flappy) = crash_after(|flappy| {
+            [CollisionLayers::OBSTACLE, CollisionLayers::GROUND]
+                .map(|layers| {
+                    CollisionStarted::overlapping(
+                        [flappy, Entity::from_raw(100)],
+                        [CollisionLayers::PLAYER, layers],
+                    )
+                })
+                .to_vec()
+        });
+
+        assert_eq!(
+            *world.resource::<State<GameState>>().current(),
+            GameState::GameOver
         );
+        assert!(!world.get::<Collider>(flappy).unwrap().enabled);
+    }
 
-        if collision.is_some() && flappy_collider.enabled {
-            if maybe_pipe_gap.is_some() {
-                scoreboard.update_current_score(1);
-                commands.entity(collider_entity).despawn();
-            } else {
-                run_state.set(GameState::GameOver).unwrap();
-            }
-        }
+    #[test]
+    fn triggers_do_not_crash() {
+        let (world, flappy) = crash_after(|flappy| {
+            vec![CollisionStarted::overlapping(
+                [flappy, Entity::from_raw(100)],
+                [CollisionLayers::PLAYER, CollisionLayers::SCORE_TRIGGER],
+            )]
+        });
+
+        assert_eq!(
+            *world.resource::<State<GameState>>().current(),
+            GameState::Playing
+        );
+        assert!(world.get::<Collider>(flappy).unwrap().enabled);
     }
 }
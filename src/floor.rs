@@ -1,13 +1,12 @@
 use bevy::prelude::*;
 
-use crate::collider::Collider;
-use crate::game_state::GameState;
+use crate::collider::{Collider, CollisionLayers};
+use crate::config::GameConfig;
+use crate::game_state::while_playing;
+use crate::physics::PhysicsStage;
 use crate::window::*;
+use crate::CameraScrollSystem;
 
-const FLOOR_WIDTH: f32 = WINDOW_WIDTH;
-const FLOOR_THICKNESS: f32 = 30.0;
-const FLOOR_POSITION_Y: f32 = -WINDOW_HEIGHT / 2.0 + (FLOOR_THICKNESS / 2.0);
-const FLOOR_STARTING_POSITION_X: f32 = -WINDOW_WIDTH / 2.0;
 const FLOOR_COLOR: Color = Color::rgb(0.5, 0.5, 0.7);
 // for infinite floor, 3 floor entities reused when one move out of the window
 const FLOOR_ENTITY_COUNT: u32 = 3;
@@ -16,7 +15,12 @@ pub struct FloorPlugin;
 
 impl Plugin for FloorPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(SystemSet::on_update(GameState::Playing).with_system(side_scroll));
+        app.add_system_to_stage(
+            PhysicsStage,
+            side_scroll
+                .with_run_criteria(while_playing)
+                .after(CameraScrollSystem),
+        );
     }
 }
 
@@ -36,15 +40,19 @@ pub struct FloorBundle {
 }
 
 impl FloorBundle {
-    pub fn new(index: u32) -> Self {
+    pub fn new(index: u32, config: &GameConfig) -> Self {
+        let floor_width = config.window_width;
+        let starting_position_x = -config.window_width / 2.0;
+        let position_y = -config.window_height / 2.0 + (config.floor_thickness / 2.0);
+
         let pos = index as f32;
-        let translation_x = FLOOR_STARTING_POSITION_X + (FLOOR_WIDTH / 2.0) + (pos * FLOOR_WIDTH);
-        let scale = Vec3::new(FLOOR_WIDTH, FLOOR_THICKNESS, 0.0);
+        let translation_x = starting_position_x + (floor_width / 2.0) + (pos * floor_width);
+        let scale = Vec3::new(floor_width, config.floor_thickness, 0.0);
 
         FloorBundle {
             sprite: SpriteBundle {
                 transform: Transform {
-                    translation: Vec3::new(translation_x, FLOOR_POSITION_Y, 2.0),
+                    translation: Vec3::new(translation_x, position_y, 2.0),
                     scale,
                     ..default()
                 },
@@ -54,7 +62,7 @@ impl FloorBundle {
                 },
                 ..default()
             },
-            collider: Collider::new(scale.truncate()),
+            collider: Collider::new(Vec2::ONE).with_layers(CollisionLayers::GROUND),
             floor: Floor,
         }
     }
@@ -64,28 +72,32 @@ impl FloorBundle {
 // -- SYSTEM
 //
 
-pub fn setup(commands: &mut Commands) {
+pub fn setup(commands: &mut Commands, config: &GameConfig) {
     for i in 0..FLOOR_ENTITY_COUNT {
         commands
             .spawn()
             .insert(Name::new("Floor"))
-            .insert_bundle(FloorBundle::new(i));
+            .insert_bundle(FloorBundle::new(i, config));
     }
 }
 
 fn side_scroll(
+    config: Res<GameConfig>,
     camera_query: Query<&Transform, With<Camera2d>>,
     mut floor_query: Query<&mut Transform, (With<Floor>, Without<Camera2d>)>,
 ) {
     let camera_transform = camera_query.single();
 
-    // when a floor moved out of sight, reuse it by moving it to the back
+    // when a floor moved out of sight, reuse it by moving it to the back.
+    // The floors keep the width they were spawned with if the config changes.
     for mut floor_transform in &mut floor_query {
-        let floor_right_edge_position = floor_transform.translation.x + (FLOOR_WIDTH / 2.0);
-        let camera_left_edge_position = camera_transform.translation.x - (WINDOW_WIDTH / 2.0);
+        let floor_width = floor_transform.scale.x;
+        let floor_right_edge_position = floor_transform.translation.x + (floor_width / 2.0);
+        let camera_left_edge_position =
+            camera_transform.translation.x - (config.window_width / 2.0);
 
         if floor_right_edge_position + WINDOW_BOUND_LIMIT < camera_left_edge_position {
-            floor_transform.translation.x += FLOOR_WIDTH * (FLOOR_ENTITY_COUNT as f32);
+            floor_transform.translation.x += floor_width * (FLOOR_ENTITY_COUNT as f32);
         }
     }
 }
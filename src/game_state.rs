@@ -1,6 +1,32 @@
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum GameState {
     Intro,
     Playing,
+    /// Pushed on top of `Playing`
+    Paused,
     GameOver,
+    Settings,
+}
+
+/// Run criteria for systems frozen while the game is paused
+pub fn unless_paused(run_state: Res<State<GameState>>) -> ShouldRun {
+    if *run_state.current() == GameState::Paused {
+        ShouldRun::No
+    } else {
+        ShouldRun::Yes
+    }
+}
+
+/// Run criteria for the gameplay systems of the `PhysicsStage`, where the
+/// `SystemSet::on_update` criteria are not available as the state is driven
+/// by `CoreStage::Update`
+pub fn while_playing(run_state: Res<State<GameState>>) -> ShouldRun {
+    if *run_state.current() == GameState::Playing {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
 }
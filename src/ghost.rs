@@ -0,0 +1,209 @@
+use bevy::prelude::*;
+
+use crate::animation::AnimationCommand;
+use crate::config::GameConfig;
+use crate::difficulty::{Difficulty, Preset};
+use crate::flappy;
+use crate::game_state::{while_playing, GameState};
+use crate::gravity::{ApplyGravitySystem, GravityAffected};
+use crate::physics::{PhysicsStage, PreviousTranslation, StorePreviousTranslationSystem};
+use crate::pipe::{self, PipeSet};
+use crate::replay::{self, Replay, ReplaySystem, RunStep};
+use crate::save::SaveStorage;
+use crate::seed::CourseRng;
+use crate::velocity::{ApplyVelocitySystem, Velocity};
+use crate::window::WINDOW_BOUND_LIMIT;
+use crate::{ResetCourseSystem, FLAPPY_STARTING_POSITION};
+
+const GHOST_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.4);
+// behind flappy and the pipes
+const GHOST_DEPTH_OFFSET: f32 = -0.5;
+
+/// Translucent flappy flying the best run of the preset, when the course has
+/// the seed of that run. It goes through everything, having no `Collider`.
+///
+/// Its flaps are replayed on the same physics steps as the recorded run, so it
+/// climbs and falls exactly like it did. Its pace ramps up with the gaps it
+/// reaches, like the recorded run did with its score, which keeps it exact as
+/// long as the course ahead was laid out at the same difficulty.
+#[derive(Component)]
+pub struct Ghost {
+    flap_steps: Vec<u32>,
+    next_flap_index: usize,
+    /// Stands for the score of the recorded run
+    passed_pipe_sets: u32,
+    last_passed_pipe_set_x: f32,
+}
+
+pub struct GhostPlugin;
+
+impl Plugin for GhostPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_enter(GameState::Playing)
+                .with_system(spawn_ghost.after(ResetCourseSystem)),
+        );
+        app.add_system_set_to_stage(
+            PhysicsStage,
+            SystemSet::new()
+                .with_run_criteria(while_playing)
+                .with_system(
+                    ghost_flap
+                        .after(StorePreviousTranslationSystem)
+                        .before(ReplaySystem)
+                        .before(ApplyGravitySystem),
+                )
+                .with_system(
+                    ghost_keep_pace
+                        .after(StorePreviousTranslationSystem)
+                        .before(ApplyVelocitySystem),
+                ),
+        );
+        app.add_system_set(SystemSet::on_enter(GameState::GameOver).with_system(freeze_ghost));
+        app.add_system(despawn_fallen_ghost);
+    }
+}
+
+fn spawn_ghost(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    storage: Res<SaveStorage>,
+    preset: Res<Preset>,
+    course_rng: Res<CourseRng>,
+    asset_server: Res<AssetServer>,
+    ghost_query: Query<Entity, With<Ghost>>,
+) {
+    for ghost_entity in &ghost_query {
+        commands.entity(ghost_entity).despawn();
+    }
+
+    let Some(Replay {
+        seed,
+        start_y,
+        start_velocity_y,
+        flap_steps,
+        ..
+    }) = replay::load_best_replay(&storage, *preset)
+    else {
+        return;
+    };
+    if seed != course_rng.seed() {
+        return;
+    }
+
+    let position = Vec3::new(
+        FLAPPY_STARTING_POSITION.x,
+        start_y,
+        FLAPPY_STARTING_POSITION.z + GHOST_DEPTH_OFFSET,
+    );
+    commands
+        .spawn()
+        .insert(Name::new("Ghost"))
+        .insert(Ghost {
+            flap_steps,
+            next_flap_index: 0,
+            passed_pipe_sets: 0,
+            last_passed_pipe_set_x: f32::MIN,
+        })
+        .insert(Velocity(Vec2::new(
+            Difficulty::starting(&config, *preset).scrolling_speed,
+            start_velocity_y,
+        )))
+        .insert(PreviousTranslation(position))
+        .insert(GravityAffected(true))
+        .insert_bundle(SpriteSheetBundle {
+            transform: Transform {
+                translation: position,
+                scale: flappy::FLAPPY_SPRITE_SCALE,
+                ..default()
+            },
+            sprite: TextureAtlasSprite {
+                color: GHOST_COLOR,
+                flip_x: true,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(flappy::animation(&asset_server, flappy::FLAP_CLIP));
+}
+
+/// Runs before the `RunStep` of this physics step is advanced
+fn ghost_flap(
+    mut animation_commands: EventWriter<AnimationCommand>,
+    run_step: Res<RunStep>,
+    difficulty: Res<Difficulty>,
+    mut query: Query<(Entity, &mut Ghost, &mut Velocity)>,
+) {
+    for (ghost_entity, mut ghost, mut velocity) in &mut query {
+        if ghost.flap_steps.get(ghost.next_flap_index) == Some(&run_step.0) {
+            ghost.next_flap_index += 1;
+            velocity.y = difficulty.flappy_jump_strength;
+            animation_commands.send(AnimationCommand::Replay(ghost_entity));
+        }
+    }
+}
+
+fn ghost_keep_pace(
+    mut animation_commands: EventWriter<AnimationCommand>,
+    config: Res<GameConfig>,
+    preset: Res<Preset>,
+    mut ghost_query: Query<(Entity, &mut Ghost, &mut Velocity, &Transform)>,
+    pipe_set_query: Query<&Transform, With<PipeSet>>,
+) {
+    for (ghost_entity, mut ghost, mut velocity, ghost_transform) in &mut ghost_query {
+        let passed_pipe_sets = ghost.passed_pipe_sets;
+        let difficulty = Difficulty::at_score(&config, *preset, ghost.passed_pipe_sets);
+        // where flappy touches the gap and scores
+        let ghost_front_x = flappy::collider()
+            .placed(&GlobalTransform::from(*ghost_transform))
+            .bounds()
+            .max
+            .x;
+        let gap_half_width = pipe::gap_width(difficulty.pipe_gap) / 2.0;
+
+        for pipe_set_transform in &pipe_set_query {
+            let pipe_set_x = pipe_set_transform.translation.x;
+            if pipe_set_x > ghost.last_passed_pipe_set_x
+                && pipe_set_x - gap_half_width < ghost_front_x
+            {
+                ghost.passed_pipe_sets += 1;
+                ghost.last_passed_pipe_set_x = pipe_set_x;
+            }
+        }
+
+        let difficulty = Difficulty::at_score(&config, *preset, ghost.passed_pipe_sets);
+        velocity.x = difficulty.scrolling_speed;
+        if ghost.passed_pipe_sets != passed_pipe_sets {
+            animation_commands.send(AnimationCommand::SetSpeed(
+                ghost_entity,
+                flappy::wing_speed(&config, &difficulty),
+            ));
+        }
+    }
+}
+
+/// The ghost stops with the course at the end of the run, wings spread
+fn freeze_ghost(
+    mut animation_commands: EventWriter<AnimationCommand>,
+    mut query: Query<(Entity, &mut Velocity, &mut GravityAffected), With<Ghost>>,
+) {
+    for (ghost_entity, mut velocity, mut gravity_affected) in &mut query {
+        velocity.0 = Vec2::ZERO;
+        gravity_affected.0 = false;
+        animation_commands.send(AnimationCommand::Seek(ghost_entity, 0));
+        animation_commands.send(AnimationCommand::SetPaused(ghost_entity, true));
+    }
+}
+
+/// Once out of its flaps, the ghost falls out of sight like the recorded run
+fn despawn_fallen_ghost(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    query: Query<(Entity, &Transform), With<Ghost>>,
+) {
+    for (ghost_entity, ghost_transform) in &query {
+        if ghost_transform.translation.y < -config.window_height / 2.0 - WINDOW_BOUND_LIMIT {
+            commands.entity(ghost_entity).despawn();
+        }
+    }
+}
@@ -1,23 +1,33 @@
-use crate::velocity::Velocity;
+use crate::difficulty::Difficulty;
+use crate::game_state::unless_paused;
+use crate::physics::{PhysicsStage, PHYSICS_DELTA_SECONDS};
+use crate::velocity::{ApplyVelocitySystem, Velocity};
 use bevy::prelude::*;
 
-const GRAVITY: f32 = 2400.0;
-
 #[derive(Component)]
 pub struct GravityAffected(pub bool);
 
 pub struct GravityPlugin;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+pub struct ApplyGravitySystem;
+
 impl Plugin for GravityPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(apply_gravity);
+        app.add_system_to_stage(
+            PhysicsStage,
+            apply_gravity
+                .with_run_criteria(unless_paused)
+                .label(ApplyGravitySystem)
+                .before(ApplyVelocitySystem),
+        );
     }
 }
 
-fn apply_gravity(time: Res<Time>, mut query: Query<(&mut Velocity, &GravityAffected)>) {
+fn apply_gravity(difficulty: Res<Difficulty>, mut query: Query<(&mut Velocity, &GravityAffected)>) {
     for (mut velocity, gravity_affected) in query.iter_mut() {
         if gravity_affected.0 {
-            velocity.y -= GRAVITY * time.delta_seconds();
+            velocity.y -= difficulty.gravity * PHYSICS_DELTA_SECONDS;
         }
     }
 }
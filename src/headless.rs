@@ -0,0 +1,188 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use bevy::asset::AssetPlugin;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::time::create_time_channels;
+
+use crate::cli;
+use crate::config;
+use crate::controls::ActionSystem;
+use crate::difficulty::{Difficulty, Preset};
+use crate::flappy::Flappy;
+use crate::game_state::GameState;
+use crate::physics::PHYSICS_TIMESTEP;
+use crate::pipe::{PipeGap, PIPE_WIDTH};
+use crate::replay::{self, ReplayPlaybackPlugin, ReplayRecorder};
+use crate::save::{self, SaveStorage};
+use crate::score::Scoreboard;
+use crate::seed::{CourseRng, SeedMode};
+use crate::velocity::Velocity;
+use crate::GamePlugin;
+
+// Stop a run that never ends, 10 minutes of game time
+const HEADLESS_DEFAULT_MAX_STEPS: u64 = 60 * 60 * 10;
+// A replayed run ends with a fall after its last flap, 10 seconds is plenty
+const REPLAY_EXTRA_STEPS: u64 = 60 * 10;
+// How far below the gap center flappy can fall before the autopilot flaps
+const AUTOPILOT_FLAP_MARGIN: f32 = 30.0;
+// How far below the gap center flappy flaps again before it starts falling
+const AUTOPILOT_CLIMB_DISTANCE: f32 = 150.0;
+
+/// Run a single game without a window or GPU, stepping the world one physics
+/// timestep per frame until flappy dies, then print the final scoreboard.
+///
+/// Usage: `bevy_flappy --headless [--steps N] [--seed N | --daily] [--preset easy|normal|hard]
+/// [--record path | --replay path]`
+///
+/// `--record` saves the replay of the run, `--replay` plays one back instead of
+/// the autopilot and fails if it does not end with the recorded score.
+pub fn run() {
+    let replay = replay::load_from_args();
+    let max_steps = cli::arg_value("--steps")
+        .map(|steps| steps.parse().expect("--steps must be a positive integer"))
+        .unwrap_or_else(|| match &replay {
+            Some(replay) => {
+                replay.flap_steps.last().copied().unwrap_or(0) as u64 + REPLAY_EXTRA_STEPS
+            }
+            None => HEADLESS_DEFAULT_MAX_STEPS,
+        });
+
+    let (time_sender, time_receiver) = create_time_channels();
+
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(TransformPlugin)
+        .add_plugin(HierarchyPlugin)
+        .add_plugin(AssetPlugin)
+        .add_plugin(bevy::input::InputPlugin)
+        .insert_resource(time_receiver)
+        .insert_resource(config::load_or_exit())
+        .insert_resource(SeedMode::from_args())
+        .insert_resource(Preset::from_args())
+        // simulated runs must not pollute the player's scores
+        .insert_resource(SaveStorage::disabled())
+        .add_plugin(GamePlugin)
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            autopilot.after(InputSystem).before(ActionSystem),
+        );
+    // the autopilot still starts the run, its flaps are ignored
+    if let Some(replay) = replay.clone() {
+        app.add_plugin(ReplayPlaybackPlugin(replay));
+    }
+
+    // one physics step per frame
+    let frame_duration = Duration::from_secs_f64(PHYSICS_TIMESTEP);
+    let start = Instant::now();
+    let mut step = 0;
+
+    while step < max_steps {
+        step += 1;
+        time_sender
+            .0
+            .send(start + frame_duration * step as u32)
+            .unwrap();
+        app.update();
+
+        if *app.world.resource::<State<GameState>>().current() == GameState::GameOver {
+            break;
+        }
+    }
+
+    let scoreboard = app.world.resource::<Scoreboard>();
+    println!(
+        "seed: {}, preset: {}, steps: {}, score: {}, best score: {}",
+        app.world.resource::<CourseRng>().seed(),
+        scoreboard.preset(),
+        step,
+        scoreboard.current_score(),
+        scoreboard.best_score()
+    );
+
+    let last_replay = app.world.resource::<ReplayRecorder>().last_replay.as_ref();
+
+    if let Some(path) = cli::arg_value("--record") {
+        let Some(last_replay) = last_replay else {
+            eprintln!("error: the run did not end, no replay to record");
+            std::process::exit(1);
+        };
+        if let Err(err) = save::write_atomically(Path::new(&path), &last_replay.to_bytes()) {
+            eprintln!("error: could not save the replay {}: {}", path, err);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(replay) = replay {
+        let replayed_score = last_replay.map(|last_replay| last_replay.score);
+        if replayed_score != Some(replay.score) {
+            eprintln!(
+                "error: the replay recorded a score of {}, the playback ended with {}",
+                replay.score,
+                replayed_score.map_or("no score, the run did not end".to_string(), |score| {
+                    score.to_string()
+                })
+            );
+            std::process::exit(1);
+        }
+        println!("replay verified, score: {}", replay.score);
+    }
+}
+
+//
+// -- SYSTEM
+//
+
+/// Stand-in for the player: press space to start, then flap whenever flappy
+/// falls below the center of the next gap, or climbs too slowly toward it.
+fn autopilot(
+    mut target_gap: Local<Option<Vec3>>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    run_state: Res<State<GameState>>,
+    difficulty: Res<Difficulty>,
+    flappy_query: Query<(&Transform, &Velocity), With<Flappy>>,
+    gap_query: Query<&GlobalTransform, With<PipeGap>>,
+) {
+    // release for a frame so the next press is a new one
+    if keyboard_input.pressed(KeyCode::Space) {
+        keyboard_input.release(KeyCode::Space);
+        return;
+    }
+
+    let should_flap = match run_state.current() {
+        GameState::Playing => {
+            let (flappy_transform, flappy_velocity) = flappy_query.single();
+            let flappy_position = flappy_transform.translation;
+            let is_passed =
+                |gap_position: &Vec3| gap_position.x + PIPE_WIDTH * 0.75 < flappy_position.x;
+
+            // gaps are despawned once scored, keep aiming at it until out of its pipes
+            if target_gap.is_none_or(|gap_position| is_passed(&gap_position)) {
+                *target_gap = gap_query
+                    .iter()
+                    .map(|gap_transform| gap_transform.translation())
+                    .filter(|gap_position| !is_passed(gap_position))
+                    .min_by(|a, b| a.x.total_cmp(&b.x));
+            }
+
+            let target_y = target_gap.map_or(0.0, |gap_position| gap_position.y);
+            // keep flapping on the way up when the gap is far above
+            let flap_velocity = if flappy_position.y < target_y - AUTOPILOT_CLIMB_DISTANCE {
+                difficulty.flappy_jump_strength / 2.0
+            } else {
+                0.0
+            };
+            flappy_velocity.y <= flap_velocity
+                && flappy_position.y < target_y - AUTOPILOT_FLAP_MARGIN
+        }
+        _ => {
+            *target_gap = None;
+            true
+        }
+    };
+
+    if should_flap {
+        keyboard_input.press(KeyCode::Space);
+    }
+}
@@ -0,0 +1,212 @@
+use bevy::prelude::*;
+
+use crate::game_state::GameState;
+use crate::score::{Scoreboard, UpdateBestScoreSystem};
+
+const HUD_FONT_PATH: &str = "fonts/FiraSans-Bold.ttf";
+const HUD_TEXT_COLOR: Color = Color::rgb(0.2, 0.2, 0.3);
+const HUD_HIGHLIGHT_COLOR: Color = Color::rgb(0.95, 0.55, 0.1);
+const HUD_SCORE_FONT_SIZE: f32 = 64.0;
+const HUD_SCORE_MARGIN_TOP: f32 = 40.0;
+const GAMEOVER_PANEL_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.8);
+const GAMEOVER_PANEL_PADDING: f32 = 30.0;
+const GAMEOVER_TITLE_FONT_SIZE: f32 = 48.0;
+const GAMEOVER_TEXT_FONT_SIZE: f32 = 32.0;
+
+/// Score display on top of the game, only added when rendering
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HudFont>();
+        app.add_system_set(SystemSet::on_enter(GameState::Playing).with_system(spawn_score_hud));
+        app.add_system_set(
+            SystemSet::on_exit(GameState::Playing).with_system(despawn_with::<ScoreHud>),
+        );
+        app.add_system_set(
+            SystemSet::on_enter(GameState::GameOver)
+                .with_system(spawn_gameover_panel.after(UpdateBestScoreSystem)),
+        );
+        app.add_system_set(
+            SystemSet::on_exit(GameState::GameOver).with_system(despawn_with::<GameOverPanel>),
+        );
+        // hidden while the settings are open on top of the game over
+        app.add_system_set(
+            SystemSet::on_pause(GameState::GameOver).with_system(despawn_with::<GameOverPanel>),
+        );
+        app.add_system_set(
+            SystemSet::on_resume(GameState::GameOver).with_system(spawn_gameover_panel),
+        );
+        app.add_system(update_score_texts);
+    }
+}
+
+pub struct HudFont(pub Handle<Font>);
+
+impl FromWorld for HudFont {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        HudFont(asset_server.load(HUD_FONT_PATH))
+    }
+}
+
+//
+// -- COMPONENT
+//
+
+#[derive(Component)]
+struct ScoreHud;
+
+#[derive(Component)]
+struct GameOverPanel;
+
+/// Text kept in sync with the `Scoreboard`
+#[derive(Component, Clone, Copy)]
+enum ScoreText {
+    Current,
+    /// Preset of the run, its best score is the one shown
+    Preset,
+    Best,
+    /// Only visible when the last run beat the best score
+    NewBest,
+}
+
+impl ScoreText {
+    fn value(&self, scoreboard: &Scoreboard) -> String {
+        match self {
+            ScoreText::Current => scoreboard.current_score().to_string(),
+            ScoreText::Preset => format!("Difficulty: {}", scoreboard.preset()),
+            ScoreText::Best => format!("Best: {}", scoreboard.best_score()),
+            ScoreText::NewBest => "New best!".to_string(),
+        }
+    }
+
+    fn is_visible(&self, scoreboard: &Scoreboard) -> bool {
+        match self {
+            ScoreText::NewBest => scoreboard.is_new_best(),
+            _ => true,
+        }
+    }
+
+    fn text_bundle(&self, scoreboard: &Scoreboard, style: TextStyle) -> TextBundle {
+        let mut text_bundle = TextBundle::from_section(self.value(scoreboard), style)
+            .with_text_alignment(TextAlignment::CENTER);
+        text_bundle.visibility.is_visible = self.is_visible(scoreboard);
+
+        text_bundle
+    }
+}
+
+//
+// -- SYSTEM
+//
+
+fn spawn_score_hud(mut commands: Commands, font: Res<HudFont>, scoreboard: Res<Scoreboard>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                // bevy ui y axis goes up, reverse the column to lay out from the top
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                padding: UiRect {
+                    top: Val::Px(HUD_SCORE_MARGIN_TOP),
+                    ..default()
+                },
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(Name::new("Score HUD"))
+        .insert(ScoreHud)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(ScoreText::Current.text_bundle(
+                    &scoreboard,
+                    TextStyle {
+                        font: font.0.clone(),
+                        font_size: HUD_SCORE_FONT_SIZE,
+                        color: HUD_TEXT_COLOR,
+                    },
+                ))
+                .insert(ScoreText::Current);
+        });
+}
+
+fn spawn_gameover_panel(mut commands: Commands, font: Res<HudFont>, scoreboard: Res<Scoreboard>) {
+    let text_style = |font_size, color| TextStyle {
+        font: font.0.clone(),
+        font_size,
+        color,
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(Name::new("Game Over Panel"))
+        .insert(GameOverPanel)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::ColumnReverse,
+                        align_items: AlignItems::Center,
+                        padding: UiRect::all(Val::Px(GAMEOVER_PANEL_PADDING)),
+                        ..default()
+                    },
+                    color: GAMEOVER_PANEL_COLOR.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn_bundle(TextBundle::from_section(
+                        "Game Over",
+                        text_style(GAMEOVER_TITLE_FONT_SIZE, HUD_TEXT_COLOR),
+                    ));
+                    for (score_text, font_size, color) in [
+                        (ScoreText::Preset, GAMEOVER_TEXT_FONT_SIZE, HUD_TEXT_COLOR),
+                        (ScoreText::Current, HUD_SCORE_FONT_SIZE, HUD_TEXT_COLOR),
+                        (ScoreText::Best, GAMEOVER_TEXT_FONT_SIZE, HUD_TEXT_COLOR),
+                        (
+                            ScoreText::NewBest,
+                            GAMEOVER_TEXT_FONT_SIZE,
+                            HUD_HIGHLIGHT_COLOR,
+                        ),
+                    ] {
+                        parent
+                            .spawn_bundle(
+                                score_text.text_bundle(&scoreboard, text_style(font_size, color)),
+                            )
+                            .insert(score_text);
+                    }
+                });
+        });
+}
+
+fn update_score_texts(
+    scoreboard: Res<Scoreboard>,
+    mut query: Query<(&ScoreText, &mut Text, &mut Visibility)>,
+) {
+    if !scoreboard.is_changed() {
+        return;
+    }
+
+    for (score_text, mut text, mut visibility) in &mut query {
+        text.sections[0].value = score_text.value(&scoreboard);
+        visibility.is_visible = score_text.is_visible(&scoreboard);
+    }
+}
+
+fn despawn_with<T: Component>(mut commands: Commands, query: Query<Entity, With<T>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,220 @@
+// Bevy queries and system params are routinely flagged by this lint
+#![allow(clippy::type_complexity)]
+
+use bevy::{asset::AssetServerSettings, prelude::*, render::texture::ImageSettings};
+use bevy_inspector_egui::WorldInspectorPlugin;
+use flappy::FlappyPlugin;
+use gravity::GravityPlugin;
+use pipe::{PipePlugin, PipeSet};
+use score::ScorePlugin;
+use velocity::VelocityPlugin;
+
+mod animation;
+mod cli;
+pub mod collider;
+pub mod collision;
+pub mod config;
+mod controls;
+mod difficulty;
+pub mod flappy;
+mod floor;
+mod game_state;
+mod ghost;
+mod gravity;
+mod headless;
+mod hud;
+mod menu;
+pub mod physics;
+pub mod pipe;
+mod replay;
+mod save;
+mod score;
+pub mod seed;
+mod velocity;
+mod window;
+
+use crate::animation::{AnimationAtlasPlugin, AnimationPlugin};
+use crate::collision::CollisionPlugin;
+use crate::config::{GameConfig, GameConfigPlugin};
+use crate::controls::ControlsPlugin;
+use crate::difficulty::{Difficulty, DifficultyPlugin, Preset};
+use crate::floor::{Floor, FloorPlugin};
+use crate::game_state::*;
+use crate::ghost::GhostPlugin;
+use crate::hud::HudPlugin;
+use crate::menu::{MenuPlugin, MenuUiPlugin};
+use crate::physics::{PhysicsPlugin, PhysicsStage, PreviousTranslation, PHYSICS_DELTA_SECONDS};
+use crate::replay::{ReplayPlaybackPlugin, ReplayPlugin};
+use crate::seed::{CourseRng, SeedMode};
+use crate::velocity::ApplyVelocitySystem;
+use crate::window::*;
+
+pub const FLAPPY_STARTING_POSITION: Vec3 = Vec2::ZERO.extend(1.0);
+
+/// Runs the game, in a window unless `--headless`
+pub fn run() {
+    if cli::has_flag("--headless") {
+        headless::run();
+        return;
+    }
+
+    let config = config::load_or_exit();
+    let replay = replay::load_from_args();
+
+    let mut app = App::new();
+    app.insert_resource(WindowDescriptor {
+        width: config.window_width,
+        height: config.window_height,
+        resizable: false,
+        ..default()
+    })
+    .insert_resource(AssetServerSettings {
+        watch_for_changes: true,
+        ..default()
+    })
+    .insert_resource(config)
+    .insert_resource(ClearColor(BACKGROUND_COLOR))
+    .insert_resource(ImageSettings::default_nearest())
+    .insert_resource(Msaa { samples: 1 })
+    .insert_resource(SeedMode::from_args())
+    .insert_resource(Preset::from_args())
+    .add_plugins(DefaultPlugins)
+    .add_plugin(WorldInspectorPlugin::new())
+    .add_plugin(GameConfigPlugin)
+    .add_plugin(GamePlugin)
+    .add_plugin(HudPlugin)
+    .add_plugin(MenuUiPlugin)
+    .add_plugin(AnimationAtlasPlugin);
+
+    if let Some(replay) = replay {
+        app.add_plugin(ReplayPlaybackPlugin(replay));
+    }
+
+    app.run();
+}
+
+/// Moves the camera along the course, the course follows it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+pub struct CameraScrollSystem;
+
+/// Replaces the course on entering `GameState::Playing`, along with the `CourseRng`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+pub struct ResetCourseSystem;
+
+/// The whole game without any rendering concerns, shared by the windowed and
+/// the headless app.
+pub struct GamePlugin;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ControlsPlugin)
+            .add_plugin(AnimationPlugin)
+            .add_plugin(PhysicsPlugin)
+            .add_plugin(CollisionPlugin)
+            .add_plugin(VelocityPlugin)
+            .add_plugin(GravityPlugin)
+            .add_plugin(FloorPlugin)
+            .add_plugin(PipePlugin)
+            .add_plugin(FlappyPlugin)
+            .add_plugin(ScorePlugin)
+            .add_plugin(DifficultyPlugin)
+            .add_plugin(ReplayPlugin)
+            .add_plugin(GhostPlugin)
+            .add_plugin(MenuPlugin)
+            .init_resource::<GameConfig>()
+            .init_resource::<SeedMode>()
+            .add_startup_system(setup)
+            .add_state(GameState::Intro)
+            .add_system_to_stage(
+                PhysicsStage,
+                camera_side_scroll
+                    .with_run_criteria(while_playing)
+                    .label(CameraScrollSystem)
+                    .after(ApplyVelocitySystem),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reset_setup)
+                    .with_system(reset_course.label(ResetCourseSystem)),
+            );
+    }
+}
+
+//
+// -- SETUP
+//
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    config: Res<GameConfig>,
+    seed_mode: Res<SeedMode>,
+    preset: Res<Preset>,
+) {
+    // Camera
+    let camera_bundle = Camera2dBundle::default();
+    commands
+        .spawn()
+        .insert(PreviousTranslation(camera_bundle.transform.translation))
+        .insert_bundle(camera_bundle);
+
+    // Flappy
+    flappy::spawn(&mut commands, &asset_server, FLAPPY_STARTING_POSITION);
+
+    let mut course_rng = CourseRng::new(seed_mode.next_seed());
+    floor::setup(&mut commands, &config);
+    pipe::setup(
+        &mut commands,
+        &config,
+        &Difficulty::starting(&config, *preset),
+        &mut course_rng,
+    );
+    commands.insert_resource(course_rng);
+}
+
+/// Start every run from the beginning, flappy is reset by `flappy_setup_playing`
+fn reset_setup(
+    mut camera_query: Query<(&mut Transform, &mut PreviousTranslation), With<Camera2d>>,
+) {
+    let (mut camera_transform, mut camera_previous_translation) = camera_query.single_mut();
+
+    let default_transform = Camera2dBundle::default().transform;
+    camera_transform.translation = default_transform.translation;
+    camera_previous_translation.0 = default_transform.translation;
+}
+
+/// Replace the floors and pipes of the last run by a fresh course
+fn reset_course(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    seed_mode: Res<SeedMode>,
+    preset: Res<Preset>,
+    mut course_rng: ResMut<CourseRng>,
+    course_query: Query<Entity, Or<(With<Floor>, With<PipeSet>)>>,
+) {
+    for course_entity in course_query.iter() {
+        commands.entity(course_entity).despawn_recursive();
+    }
+
+    *course_rng = CourseRng::new(seed_mode.next_seed());
+    floor::setup(&mut commands, &config);
+    pipe::setup(
+        &mut commands,
+        &config,
+        &Difficulty::starting(&config, *preset),
+        &mut course_rng,
+    );
+}
+
+//
+// -- SYSTEM
+//
+
+fn camera_side_scroll(
+    difficulty: Res<Difficulty>,
+    mut query: Query<&mut Transform, With<Camera2d>>,
+) {
+    let mut camera_transform = query.single_mut();
+
+    camera_transform.translation.x += difficulty.scrolling_speed * PHYSICS_DELTA_SECONDS;
+}
@@ -0,0 +1,501 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::window::WindowFocused;
+
+use crate::controls::{Action, ActionBindings};
+use crate::difficulty::Preset;
+use crate::game_state::GameState;
+use crate::hud::HudFont;
+use crate::seed::SeedMode;
+use crate::window::BACKGROUND_COLOR;
+
+// Ignore menu input right after entering a menu, so a flap does not skip it
+const MENU_TRANSITION_DELAY: f32 = 0.45;
+
+const MENU_TEXT_COLOR: Color = Color::rgb(0.2, 0.2, 0.3);
+const MENU_TITLE_FONT_SIZE: f32 = 72.0;
+const MENU_TEXT_FONT_SIZE: f32 = 28.0;
+const MENU_PADDING: f32 = 40.0;
+const BUTTON_SIZE: Size<Val> = Size {
+    width: Val::Px(240.0),
+    height: Val::Px(50.0),
+};
+const BUTTON_MARGIN: f32 = 8.0;
+const BUTTON_COLOR: Color = Color::rgb(0.6, 0.85, 0.4);
+const BUTTON_SELECTED_COLOR: Color = Color::rgb(0.95, 0.55, 0.1);
+const BUTTON_TEXT_COLOR: Color = Color::WHITE;
+const PAUSE_OVERLAY_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.6);
+
+/// Menu navigation and actions, shared by the windowed and the headless app
+pub struct MenuPlugin;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+pub enum MenuSystem {
+    Reset,
+    Input,
+    Apply,
+}
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MenuSelection>();
+        app.add_event::<MenuActionEvent>();
+        app.add_startup_system(setup);
+        app.add_system(reset_menu.label(MenuSystem::Reset));
+        app.add_system(menu_input.label(MenuSystem::Input).after(MenuSystem::Reset));
+        app.add_system(
+            pause_on_gamepad_disconnected
+                .label(MenuSystem::Input)
+                .after(MenuSystem::Reset),
+        );
+        app.add_system(
+            apply_menu_action
+                .label(MenuSystem::Apply)
+                .after(MenuSystem::Input),
+        );
+    }
+}
+
+/// Menu screens, mouse input and pausing on window focus loss, only added when
+/// running with a window
+pub struct MenuUiPlugin;
+
+impl Plugin for MenuUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HudFont>();
+        app.add_system(spawn_menu_screen.after(MenuSystem::Reset));
+        // after the other inputs, so a click on a button wins over the selection
+        app.add_system(
+            menu_mouse_input
+                .after(MenuSystem::Input)
+                .before(MenuSystem::Apply),
+        );
+        app.add_system(
+            pause_on_focus_lost
+                .label(MenuSystem::Input)
+                .after(MenuSystem::Reset),
+        );
+        app.add_system(update_menu_buttons.after(MenuSystem::Apply));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuAction {
+    Start,
+    /// Not in any menu, triggered by its shortcut while playing
+    Pause,
+    Resume,
+    Restart,
+    Retry,
+    Settings,
+    ToggleCourse,
+    /// Switch to the next difficulty preset, applied from the next run
+    TogglePreset,
+    Back,
+    Quit,
+}
+
+impl MenuAction {
+    fn label(&self, seed_mode: &SeedMode, preset: Preset) -> String {
+        match self {
+            MenuAction::Start => "Start".to_string(),
+            MenuAction::Pause => "Pause".to_string(),
+            MenuAction::Resume => "Resume".to_string(),
+            MenuAction::Restart => "Restart".to_string(),
+            MenuAction::Retry => "Retry".to_string(),
+            MenuAction::Settings => "Settings".to_string(),
+            MenuAction::ToggleCourse => match seed_mode {
+                SeedMode::Random => "Course: Random".to_string(),
+                SeedMode::Daily => "Course: Daily".to_string(),
+                SeedMode::Fixed(seed) => format!("Course: Seed {}", seed),
+            },
+            MenuAction::TogglePreset => format!("Difficulty: {}", preset),
+            MenuAction::Back => "Back".to_string(),
+            MenuAction::Quit => "Quit".to_string(),
+        }
+    }
+}
+
+/// Actions of the menu shown in `state`, the first one is selected by default
+fn menu_actions(state: &GameState) -> &'static [MenuAction] {
+    match state {
+        GameState::Intro => &[
+            MenuAction::Start,
+            MenuAction::TogglePreset,
+            MenuAction::Settings,
+        ],
+        GameState::Playing => &[],
+        GameState::Paused => &[MenuAction::Resume, MenuAction::Restart, MenuAction::Quit],
+        GameState::GameOver => &[MenuAction::Retry, MenuAction::Settings, MenuAction::Quit],
+        GameState::Settings => &[
+            MenuAction::ToggleCourse,
+            MenuAction::TogglePreset,
+            MenuAction::Back,
+        ],
+    }
+}
+
+/// Index of the highlighted action in the current menu
+#[derive(Default)]
+pub struct MenuSelection(pub usize);
+
+pub struct MenuActionEvent(pub MenuAction);
+
+//
+// -- COMPONENT
+//
+
+#[derive(Component)]
+struct MenuTransitionDelay {
+    timer: Timer,
+}
+
+/// Root of the UI of a menu, despawned when leaving the menu
+#[derive(Component)]
+struct MenuScreen;
+
+#[derive(Component)]
+struct MenuButton {
+    index: usize,
+    action: MenuAction,
+}
+
+//
+// -- SYSTEM
+//
+
+fn setup(mut commands: Commands) {
+    commands.spawn().insert(MenuTransitionDelay {
+        timer: Timer::from_seconds(MENU_TRANSITION_DELAY, false),
+    });
+}
+
+/// Reset the selection and the transition delay whenever another menu shows up,
+/// including when coming back to a paused one
+fn reset_menu(
+    mut last_state: Local<Option<GameState>>,
+    run_state: Res<State<GameState>>,
+    mut selection: ResMut<MenuSelection>,
+    mut delay_query: Query<&mut MenuTransitionDelay>,
+) {
+    if last_state.as_ref() == Some(run_state.current()) {
+        return;
+    }
+    *last_state = Some(run_state.current().clone());
+
+    selection.0 = 0;
+    delay_query.single_mut().timer.reset();
+}
+
+fn menu_input(
+    actions: Res<Input<Action>>,
+    run_state: Res<State<GameState>>,
+    mut selection: ResMut<MenuSelection>,
+    mut action_events: EventWriter<MenuActionEvent>,
+) {
+    match run_state.current() {
+        GameState::Playing if actions.just_pressed(Action::Pause) => {
+            action_events.send(MenuActionEvent(MenuAction::Pause))
+        }
+        GameState::Paused if actions.any_just_pressed([Action::Pause, Action::Back]) => {
+            action_events.send(MenuActionEvent(MenuAction::Resume))
+        }
+        GameState::Settings if actions.just_pressed(Action::Back) => {
+            action_events.send(MenuActionEvent(MenuAction::Back))
+        }
+        _ => {}
+    }
+
+    let menu_actions = menu_actions(run_state.current());
+    if menu_actions.is_empty() {
+        return;
+    }
+
+    if actions.just_pressed(Action::Up) {
+        selection.0 = (selection.0 + menu_actions.len() - 1) % menu_actions.len();
+    }
+    if actions.just_pressed(Action::Down) {
+        selection.0 = (selection.0 + 1) % menu_actions.len();
+    }
+    if actions.just_pressed(Action::Confirm) {
+        action_events.send(MenuActionEvent(menu_actions[selection.0]));
+    }
+}
+
+fn apply_menu_action(
+    time: Res<Time>,
+    mut action_events: EventReader<MenuActionEvent>,
+    mut run_state: ResMut<State<GameState>>,
+    mut seed_mode: ResMut<SeedMode>,
+    mut preset: ResMut<Preset>,
+    mut app_exit_events: EventWriter<AppExit>,
+    mut delay_query: Query<&mut MenuTransitionDelay>,
+) {
+    let mut delay = delay_query.single_mut();
+    delay.timer.tick(time.delta());
+
+    // a single state change can be queued per frame
+    let Some(MenuActionEvent(action)) = action_events.iter().last() else {
+        return;
+    };
+    let is_available = match action {
+        MenuAction::Pause => *run_state.current() == GameState::Playing,
+        _ => delay.timer.finished() && menu_actions(run_state.current()).contains(action),
+    };
+    if !is_available {
+        return;
+    }
+
+    // refused while another state change is queued or in progress, like the
+    // game over of a crash on the same frame
+    let state_change = match action {
+        MenuAction::Start | MenuAction::Retry => run_state.set(GameState::Playing),
+        MenuAction::Pause => run_state.push(GameState::Paused),
+        MenuAction::Resume => run_state.pop(),
+        // leave the paused run for a new one, restarting the course on enter
+        MenuAction::Restart => run_state.replace(GameState::Playing),
+        MenuAction::Settings => run_state.push(GameState::Settings),
+        MenuAction::Back => run_state.pop(),
+        MenuAction::ToggleCourse => {
+            *seed_mode = match *seed_mode {
+                SeedMode::Random => SeedMode::Daily,
+                SeedMode::Daily | SeedMode::Fixed(_) => SeedMode::Random,
+            };
+            Ok(())
+        }
+        MenuAction::TogglePreset => {
+            *preset = preset.next();
+            Ok(())
+        }
+        MenuAction::Quit => {
+            app_exit_events.send(AppExit);
+            Ok(())
+        }
+    };
+    if let Err(err) = state_change {
+        debug!("menu action {:?} refused: {}", action, err);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_menu_screen(
+    mut commands: Commands,
+    mut last_state: Local<Option<GameState>>,
+    font: Res<HudFont>,
+    run_state: Res<State<GameState>>,
+    seed_mode: Res<SeedMode>,
+    preset: Res<Preset>,
+    action_bindings: Res<ActionBindings>,
+    screen_query: Query<Entity, With<MenuScreen>>,
+) {
+    let state = run_state.current();
+    if last_state.as_ref() == Some(state) {
+        return;
+    }
+    *last_state = Some(state.clone());
+
+    for screen_entity in &screen_query {
+        commands.entity(screen_entity).despawn_recursive();
+    }
+
+    let actions = menu_actions(state);
+    if actions.is_empty() {
+        return;
+    }
+
+    let text_style = |font_size, color| TextStyle {
+        font: font.0.clone(),
+        font_size,
+        color,
+    };
+
+    let start_prompt = match action_bindings.first(Action::Confirm) {
+        Some(binding) => format!("Press {} to start", binding),
+        None => "Press confirm to start".to_string(),
+    };
+    let (title, subtitle, background_color, justify_content) = match state {
+        GameState::Intro => (
+            Some("Flappy"),
+            Some(start_prompt.as_str()),
+            Color::NONE,
+            JustifyContent::Center,
+        ),
+        // the game over card is shown by the hud, keep the buttons below it
+        GameState::GameOver => (None, None, Color::NONE, JustifyContent::FlexEnd),
+        GameState::Settings => (
+            Some("Settings"),
+            None,
+            BACKGROUND_COLOR,
+            JustifyContent::Center,
+        ),
+        GameState::Paused => (
+            Some("Paused"),
+            None,
+            PAUSE_OVERLAY_COLOR,
+            JustifyContent::Center,
+        ),
+        GameState::Playing => unreachable!("playing has no menu"),
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                // bevy ui y axis goes up, reverse the column to lay out from the top
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content,
+                align_items: AlignItems::Center,
+                padding: UiRect::all(Val::Px(MENU_PADDING)),
+                ..default()
+            },
+            color: background_color.into(),
+            ..default()
+        })
+        .insert(Name::new("Menu Screen"))
+        .insert(MenuScreen)
+        .with_children(|parent| {
+            if let Some(title) = title {
+                parent.spawn_bundle(TextBundle::from_section(
+                    title,
+                    text_style(MENU_TITLE_FONT_SIZE, MENU_TEXT_COLOR),
+                ));
+            }
+            if let Some(subtitle) = subtitle {
+                parent.spawn_bundle(TextBundle::from_section(
+                    subtitle,
+                    text_style(MENU_TEXT_FONT_SIZE, MENU_TEXT_COLOR),
+                ));
+            }
+
+            for (index, action) in actions.iter().enumerate() {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: BUTTON_SIZE,
+                            margin: UiRect::all(Val::Px(BUTTON_MARGIN)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        color: BUTTON_COLOR.into(),
+                        ..default()
+                    })
+                    .insert(MenuButton {
+                        index,
+                        action: *action,
+                    })
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle::from_section(
+                            action.label(&seed_mode, *preset),
+                            text_style(MENU_TEXT_FONT_SIZE, BUTTON_TEXT_COLOR),
+                        ));
+                    });
+            }
+        });
+}
+
+fn menu_mouse_input(
+    mut selection: ResMut<MenuSelection>,
+    mut action_events: EventWriter<MenuActionEvent>,
+    button_query: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+) {
+    for (interaction, button) in &button_query {
+        match interaction {
+            Interaction::Clicked => action_events.send(MenuActionEvent(button.action)),
+            Interaction::Hovered => selection.0 = button.index,
+            Interaction::None => {}
+        }
+    }
+}
+
+fn pause_on_gamepad_disconnected(
+    mut gamepad_events: EventReader<GamepadEvent>,
+    mut action_events: EventWriter<MenuActionEvent>,
+) {
+    let is_disconnected = |event: &GamepadEvent| event.event_type == GamepadEventType::Disconnected;
+
+    if gamepad_events.iter().any(is_disconnected) {
+        // only applied while playing
+        action_events.send(MenuActionEvent(MenuAction::Pause));
+    }
+}
+
+fn pause_on_focus_lost(
+    mut focus_events: EventReader<WindowFocused>,
+    mut action_events: EventWriter<MenuActionEvent>,
+) {
+    if focus_events.iter().any(|event| !event.focused) {
+        // only applied while playing
+        action_events.send(MenuActionEvent(MenuAction::Pause));
+    }
+}
+
+fn update_menu_buttons(
+    selection: Res<MenuSelection>,
+    seed_mode: Res<SeedMode>,
+    preset: Res<Preset>,
+    mut button_query: Query<(&MenuButton, &mut UiColor, &Children)>,
+    mut text_query: Query<&mut Text>,
+) {
+    for (button, mut color, children) in &mut button_query {
+        *color = if button.index == selection.0 {
+            BUTTON_SELECTED_COLOR.into()
+        } else {
+            BUTTON_COLOR.into()
+        };
+
+        if seed_mode.is_changed() || preset.is_changed() {
+            for child in children {
+                if let Ok(mut text) = text_query.get_mut(*child) {
+                    text.sections[0].value = button.action.label(&seed_mode, *preset);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collider::CollisionLayers;
+    use crate::collision::CollisionStarted;
+    use crate::flappy::{self, Flappy};
+
+    #[test]
+    fn pause_on_the_frame_of_a_crash_is_refused() {
+        let mut world = World::new();
+        world.insert_resource(State::new(GameState::Playing));
+        world.insert_resource(Time::default());
+        world.insert_resource(SeedMode::Random);
+        world.insert_resource(Preset::default());
+        world.init_resource::<Events<CollisionStarted>>();
+        world.init_resource::<Events<MenuActionEvent>>();
+        world.init_resource::<Events<AppExit>>();
+        let flappy = world.spawn().insert(Flappy).insert(flappy::collider()).id();
+        let mut physics_stage = SystemStage::single_threaded().with_system(flappy::flappy_crash);
+        let mut update_stage = SystemStage::single_threaded()
+            .with_system_set(State::<GameState>::get_driver())
+            .with_system(apply_menu_action);
+        world.spawn().insert(MenuTransitionDelay {
+            timer: Timer::from_seconds(MENU_TRANSITION_DELAY, false),
+        });
+        // entered
+        update_stage.run(&mut world);
+
+        world
+            .resource_mut::<Events<CollisionStarted>>()
+            .send(CollisionStarted::overlapping(
+                [flappy, Entity::from_raw(100)],
+                [CollisionLayers::PLAYER, CollisionLayers::OBSTACLE],
+            ));
+        physics_stage.run(&mut world);
+        world
+            .resource_mut::<Events<MenuActionEvent>>()
+            .send(MenuActionEvent(MenuAction::Pause));
+        update_stage.run(&mut world);
+
+        let run_state = world.resource::<State<GameState>>();
+        assert_eq!(*run_state.current(), GameState::GameOver);
+        assert!(run_state.inactives().is_empty());
+    }
+}
@@ -0,0 +1,77 @@
+use bevy::prelude::*;
+use bevy::time::{FixedTimestep, FixedTimesteps};
+use bevy::transform::{transform_propagate_system, TransformSystem};
+
+/// Physics runs at this fixed rate whatever the frame rate is
+pub const PHYSICS_TIMESTEP: f64 = 1.0 / 60.0;
+pub const PHYSICS_DELTA_SECONDS: f32 = PHYSICS_TIMESTEP as f32;
+const PHYSICS_TIMESTEP_LABEL: &str = "physics_timestep";
+
+/// Stage running zero or more times per frame, once for every elapsed
+/// `PHYSICS_TIMESTEP`. The whole run is simulated here, so it plays the same
+/// whatever the frame rate is.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+pub struct PhysicsStage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+pub struct StorePreviousTranslationSystem;
+
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_stage_before(
+            CoreStage::Update,
+            PhysicsStage,
+            SystemStage::parallel().with_run_criteria(
+                FixedTimestep::step(PHYSICS_TIMESTEP).with_label(PHYSICS_TIMESTEP_LABEL),
+            ),
+        );
+        app.add_system_to_stage(
+            PhysicsStage,
+            store_previous_translation.label(StorePreviousTranslationSystem),
+        );
+        // entities spawned by the previous step get their `GlobalTransform`
+        // before the collisions are checked
+        app.add_system_to_stage(
+            PhysicsStage,
+            transform_propagate_system
+                .label(TransformSystem::TransformPropagate)
+                .after(StorePreviousTranslationSystem),
+        );
+        app.add_system_to_stage(CoreStage::Last, interpolate_translation);
+    }
+}
+
+/// Translation at the previous physics step, used to smooth the rendered
+/// position of an entity in between physics steps.
+///
+/// Must be set along with `Transform` when teleporting the entity.
+#[derive(Component, Deref, DerefMut, Debug)]
+pub struct PreviousTranslation(pub Vec3);
+
+/// Keeps running while paused, so the rendered position settles on the physics one
+fn store_previous_translation(mut query: Query<(&Transform, &mut PreviousTranslation)>) {
+    for (transform, mut previous_translation) in &mut query {
+        previous_translation.0 = transform.translation;
+    }
+}
+
+/// `Transform` stays the physics state, only the `GlobalTransform` used for
+/// rendering is interpolated. Runs after every game system so they all see the
+/// physics state. Expects entities without a parent.
+fn interpolate_translation(
+    fixed_timesteps: Res<FixedTimesteps>,
+    mut query: Query<(&Transform, &PreviousTranslation, &mut GlobalTransform)>,
+) {
+    let alpha = fixed_timesteps
+        .get(PHYSICS_TIMESTEP_LABEL)
+        .map_or(1.0, |timestep| timestep.overstep_percentage() as f32);
+
+    for (transform, previous_translation, mut global_transform) in &mut query {
+        *global_transform = GlobalTransform::from(Transform {
+            translation: previous_translation.lerp(transform.translation, alpha),
+            ..*transform
+        });
+    }
+}
@@ -1,25 +1,31 @@
 use bevy::prelude::*;
 use rand::Rng;
 
-use crate::collider::Collider;
-use crate::game_state::GameState;
+use crate::collider::{Collider, CollisionLayers};
+use crate::config::GameConfig;
+use crate::difficulty::Difficulty;
+use crate::game_state::while_playing;
+use crate::physics::PhysicsStage;
+use crate::seed::CourseRng;
 use crate::window::*;
+use crate::CameraScrollSystem;
 
-const PIPE_GAP: f32 = 200.0;
 pub const PIPE_WIDTH: f32 = 125.0;
-const PIPE_GAP_MIN_Y: f32 = -200.0;
-const PIPE_GAP_MAX_Y: f32 = 200.0;
 const PIPE_COLOR: Color = Color::rgb(0.6, 0.85, 0.4);
 
 const PIPE_SET_ENTITY_COUNT: u32 = 3;
-const PIPE_DISTANCE: f32 = 350.0;
 const DISTANCE_TO_FIRST_PIPE: f32 = 500.0;
 
 pub struct PipePlugin;
 
 impl Plugin for PipePlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(SystemSet::on_update(GameState::Playing).with_system(side_scroll));
+        app.add_system_to_stage(
+            PhysicsStage,
+            side_scroll
+                .with_run_criteria(while_playing)
+                .after(CameraScrollSystem),
+        );
     }
 }
 
@@ -54,11 +60,11 @@ impl Pipe {
         }
     }
 
-    fn sprite_bundle(&self, gap_center: &Vec2) -> SpriteBundle {
+    fn sprite_bundle(&self, gap_center: &Vec2, pipe_gap: f32, config: &GameConfig) -> SpriteBundle {
         match self.position {
             PipePosition::Top => {
-                let pipe_bottom_y = gap_center.y + PIPE_GAP / 2.0;
-                let window_top = WINDOW_HEIGHT / 2.0;
+                let pipe_bottom_y = gap_center.y + pipe_gap / 2.0;
+                let window_top = config.window_height / 2.0;
                 let height_to_top = window_top - pipe_bottom_y;
                 let pipe_height = height_to_top + WINDOW_BOUND_LIMIT;
                 let pipe_y = pipe_bottom_y + pipe_height / 2.0;
@@ -70,8 +76,8 @@ impl Pipe {
             }
 
             PipePosition::Bottom => {
-                let pipe_top_y = gap_center.y - PIPE_GAP / 2.0;
-                let window_bottom = -WINDOW_HEIGHT / 2.0;
+                let pipe_top_y = gap_center.y - pipe_gap / 2.0;
+                let window_bottom = -config.window_height / 2.0;
                 let height_to_bottom = pipe_top_y - window_bottom;
                 let pipe_height = height_to_bottom + WINDOW_BOUND_LIMIT;
                 let pipe_y = pipe_top_y - pipe_height / 2.0;
@@ -94,7 +100,7 @@ pub struct PipeBundle {
 }
 
 impl PipeBundle {
-    pub fn new_set(gap_center: &Vec2) -> (Self, Self) {
+    pub fn new_set(gap_center: &Vec2, pipe_gap: f32, config: &GameConfig) -> (Self, Self) {
         let top_pipe = Pipe {
             position: PipePosition::Top,
         };
@@ -104,25 +110,13 @@ impl PipeBundle {
 
         (
             PipeBundle {
-                sprite: top_pipe.sprite_bundle(gap_center),
-                collider: Collider::new(
-                    top_pipe
-                        .sprite_bundle(gap_center)
-                        .transform
-                        .scale
-                        .truncate(),
-                ),
+                sprite: top_pipe.sprite_bundle(gap_center, pipe_gap, config),
+                collider: Collider::new(Vec2::ONE),
                 pipe: top_pipe,
             },
             PipeBundle {
-                sprite: bottom_pipe.sprite_bundle(gap_center),
-                collider: Collider::new(
-                    bottom_pipe
-                        .sprite_bundle(gap_center)
-                        .transform
-                        .scale
-                        .truncate(),
-                ),
+                sprite: bottom_pipe.sprite_bundle(gap_center, pipe_gap, config),
+                collider: Collider::new(Vec2::ONE),
                 pipe: bottom_pipe,
             },
         )
@@ -140,9 +134,14 @@ pub struct PipeGapBundle {
     collider: Collider,
 }
 
+/// Width of the scoring area between two pipes, for an opening of `pipe_gap`
+pub fn gap_width(pipe_gap: f32) -> f32 {
+    pipe_gap * 0.35
+}
+
 impl PipeGapBundle {
-    fn new(position_y: f32) -> Self {
-        let gap_scale = Vec3::new(PIPE_GAP * 0.35, PIPE_GAP, 0.0);
+    fn new(position_y: f32, pipe_gap: f32) -> Self {
+        let gap_scale = Vec3::new(gap_width(pipe_gap), pipe_gap, 0.0);
 
         PipeGapBundle {
             pipe_gap: PipeGap,
@@ -155,7 +154,9 @@ impl PipeGapBundle {
                 visibility: Visibility { is_visible: false },
                 ..default()
             },
-            collider: Collider::new(gap_scale.truncate()),
+            collider: Collider::new(Vec2::ONE)
+                .with_layers(CollisionLayers::SCORE_TRIGGER)
+                .into_sensor(),
         }
     }
 }
@@ -183,13 +184,22 @@ impl PipeSetBundle {
         }
     }
 
-    pub fn spawn(commands: &mut Commands, position_x: f32) {
+    pub fn spawn(
+        commands: &mut Commands,
+        config: &GameConfig,
+        difficulty: &Difficulty,
+        course_rng: &mut CourseRng,
+        position_x: f32,
+    ) {
         let gap_position = Vec2::new(
             position_x,
-            rand::thread_rng().gen_range(PIPE_GAP_MIN_Y..=PIPE_GAP_MAX_Y),
+            course_rng
+                .rng()
+                .gen_range(difficulty.pipe_gap_min_y..=difficulty.pipe_gap_max_y),
         );
 
-        let (top_pipe, bottom_pipe) = PipeBundle::new_set(&gap_position);
+        let (top_pipe, bottom_pipe) =
+            PipeBundle::new_set(&gap_position, difficulty.pipe_gap, config);
 
         commands
             .spawn()
@@ -210,7 +220,7 @@ impl PipeSetBundle {
                 parent
                     .spawn()
                     .insert(Name::new("Pipe Gap"))
-                    .insert_bundle(PipeGapBundle::new(gap_position.y));
+                    .insert_bundle(PipeGapBundle::new(gap_position.y, difficulty.pipe_gap));
             });
     }
 }
@@ -219,30 +229,51 @@ impl PipeSetBundle {
 // -- SYSTEM
 //
 
-pub fn setup(commands: &mut Commands) {
+pub fn setup(
+    commands: &mut Commands,
+    config: &GameConfig,
+    difficulty: &Difficulty,
+    course_rng: &mut CourseRng,
+) {
     for i in 0..PIPE_SET_ENTITY_COUNT {
-        let gap_position_x = DISTANCE_TO_FIRST_PIPE + (PIPE_DISTANCE * (i as f32));
-        PipeSetBundle::spawn(commands, gap_position_x)
+        let gap_position_x = DISTANCE_TO_FIRST_PIPE + (difficulty.pipe_distance * (i as f32));
+        PipeSetBundle::spawn(commands, config, difficulty, course_rng, gap_position_x)
     }
 }
 
 pub fn side_scroll(
     mut commands: Commands,
+    config: Res<GameConfig>,
+    difficulty: Res<Difficulty>,
+    mut course_rng: ResMut<CourseRng>,
     camera_query: Query<&Transform, With<Camera2d>>,
     pipe_sets_query: Query<(Entity, &Transform), (With<PipeSet>, Without<Camera2d>)>,
 ) {
     let camera_transform = camera_query.single();
+    // spaced from the last pipe set, so a new pipe distance applies from the next pipe
+    // without moving the pipes already ahead
+    let mut last_pipe_position_x = pipe_sets_query
+        .iter()
+        .map(|(_, pipe_transform)| pipe_transform.translation.x)
+        .fold(f32::MIN, f32::max);
 
     // when a pipe moved out of sight, despawn it and spawn a new one at the back
     for (pipe_sets_entity, pipe_transform) in &pipe_sets_query {
         let pipe_right_edge_position = pipe_transform.translation.x + (PIPE_WIDTH / 2.0);
-        let camera_left_edge_position = camera_transform.translation.x - (WINDOW_WIDTH / 2.0);
+        let camera_left_edge_position =
+            camera_transform.translation.x - (config.window_width / 2.0);
 
         if pipe_right_edge_position + WINDOW_BOUND_LIMIT < camera_left_edge_position {
-            let new_gap_position_x =
-                pipe_transform.translation.x + PIPE_DISTANCE * (PIPE_SET_ENTITY_COUNT as f32);
-
-            PipeSetBundle::spawn(&mut commands, new_gap_position_x);
+            let new_gap_position_x = last_pipe_position_x + difficulty.pipe_distance;
+            last_pipe_position_x = new_gap_position_x;
+
+            PipeSetBundle::spawn(
+                &mut commands,
+                &config,
+                &difficulty,
+                &mut course_rng,
+                new_gap_position_x,
+            );
             commands.entity(pipe_sets_entity).despawn_recursive();
         }
     }
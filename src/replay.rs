@@ -0,0 +1,456 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use bevy::prelude::*;
+
+use crate::cli;
+use crate::difficulty::Preset;
+use crate::flappy::{FlapRequest, FlapSystem, Flappy};
+use crate::game_state::{while_playing, GameState};
+use crate::physics::{PhysicsStage, PreviousTranslation, StorePreviousTranslationSystem};
+use crate::save::SaveStorage;
+use crate::score::{Scoreboard, UpdateBestScoreSystem};
+use crate::seed::{CourseRng, SeedMode};
+use crate::velocity::Velocity;
+
+const REPLAY_MAGIC: &[u8; 4] = b"FLPR";
+const REPLAY_VERSION: u8 = 2;
+const LAST_REPLAY_FILE_NAME: &str = "last.replay";
+
+/// A run, played again exactly from its course, how flappy started and the
+/// physics steps where it flapped. Only valid with the game config it was
+/// recorded with.
+///
+/// Saved in a compact binary format:
+/// - the `FLPR` magic and a version byte
+/// - the seed, 8 bytes little endian, and the preset, 1 byte
+/// - the start height and vertical velocity, 4 bytes little endian each
+/// - the score, the flap count, then every flap step as the difference with
+///   the previous one, all LEB128 varints
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    pub preset: Preset,
+    /// Height of flappy at the first physics step, a first run starts mid-hover
+    pub start_y: f32,
+    pub start_velocity_y: f32,
+    /// Final score of the run, a playback ends with the same one
+    pub score: u32,
+    /// Physics steps since the start of the run, in increasing order
+    pub flap_steps: Vec<u32>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Read(io::Error),
+    /// Not a replay, or one from another version of the game
+    Invalid(&'static str),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Read(err) => err.fmt(f),
+            ReplayError::Invalid(reason) => write!(f, "not a valid replay, {}", reason),
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Self, ReplayError> {
+        let bytes = fs::read(path).map_err(ReplayError::Read)?;
+
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(REPLAY_MAGIC.len() + 16 + self.flap_steps.len());
+        bytes.extend_from_slice(REPLAY_MAGIC);
+        bytes.push(REPLAY_VERSION);
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.push(preset_to_byte(self.preset));
+        bytes.extend_from_slice(&self.start_y.to_le_bytes());
+        bytes.extend_from_slice(&self.start_velocity_y.to_le_bytes());
+        write_varint(&mut bytes, self.score);
+        write_varint(&mut bytes, self.flap_steps.len() as u32);
+
+        let mut previous_step = 0;
+        for step in &self.flap_steps {
+            write_varint(&mut bytes, step - previous_step);
+            previous_step = *step;
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ReplayError> {
+        let mut reader = ReplayReader { bytes };
+
+        if reader.take(REPLAY_MAGIC.len())? != REPLAY_MAGIC {
+            return Err(ReplayError::Invalid("unknown file type"));
+        }
+        let version = reader.take(1)?[0];
+        if version != REPLAY_VERSION {
+            return Err(ReplayError::Invalid("unsupported version"));
+        }
+
+        let seed = u64::from_le_bytes(reader.take(8)?.try_into().unwrap());
+        let preset = *Preset::ALL
+            .get(reader.take(1)?[0] as usize)
+            .ok_or(ReplayError::Invalid("unknown preset"))?;
+        let start_y = reader.f32()?;
+        let start_velocity_y = reader.f32()?;
+        let score = reader.varint()?;
+
+        let flap_count = reader.varint()?;
+        let mut flap_steps = Vec::new();
+        let mut step: u32 = 0;
+        for _ in 0..flap_count {
+            step = step
+                .checked_add(reader.varint()?)
+                .ok_or(ReplayError::Invalid("flap step out of range"))?;
+            flap_steps.push(step);
+        }
+
+        if !reader.bytes.is_empty() {
+            return Err(ReplayError::Invalid("unexpected data after the flaps"));
+        }
+
+        Ok(Replay {
+            seed,
+            preset,
+            start_y,
+            start_velocity_y,
+            score,
+            flap_steps,
+        })
+    }
+}
+
+fn preset_to_byte(preset: Preset) -> u8 {
+    Preset::ALL
+        .iter()
+        .position(|known_preset| *known_preset == preset)
+        .unwrap() as u8
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        bytes.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+struct ReplayReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ReplayReader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], ReplayError> {
+        if self.bytes.len() < length {
+            return Err(ReplayError::Invalid("truncated file"));
+        }
+
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn f32(&mut self) -> Result<f32, ReplayError> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn varint(&mut self) -> Result<u32, ReplayError> {
+        let mut value: u32 = 0;
+
+        for shift in (0..32).step_by(7) {
+            let byte = self.take(1)?[0];
+            let bits = (byte & 0x7f) as u32;
+            // the last byte only holds the 4 highest bits
+            if shift == 28 && bits > 0x0f {
+                break;
+            }
+            value |= bits << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(ReplayError::Invalid("number out of range"))
+    }
+}
+
+/// `--replay path` plays back a saved run. A broken replay stops the game with
+/// the reason.
+pub fn load_from_args() -> Option<Replay> {
+    let path = cli::arg_value("--replay")?;
+
+    Some(Replay::load(Path::new(&path)).unwrap_or_else(|err| {
+        eprintln!("error: could not load the replay {}: {}", path, err);
+        std::process::exit(1);
+    }))
+}
+
+/// Index of the next physics step of the current run
+#[derive(Debug, Default)]
+pub struct RunStep(pub u32);
+
+/// Flaps of the current run. The finished run is kept in `last_replay` until
+/// the next one ends.
+#[derive(Debug, Default)]
+pub struct ReplayRecorder {
+    start_y: f32,
+    start_velocity_y: f32,
+    flap_steps: Vec<u32>,
+    pub last_replay: Option<Replay>,
+}
+
+/// Drives the flaps from a replay instead of the player's input
+#[derive(Debug)]
+pub struct ReplayPlayer {
+    replay: Replay,
+    next_flap_index: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        ReplayPlayer {
+            replay,
+            next_flap_index: 0,
+        }
+    }
+}
+
+/// Records every run, and saves it to `last.replay`, and `best-<preset>.replay`
+/// on a new best score
+pub struct ReplayPlugin;
+
+/// Sets the start of flappy and the `FlapRequest` of a physics step from the
+/// replay being played, then records them and advances the `RunStep`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+pub struct ReplaySystem;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunStep>();
+        app.init_resource::<ReplayRecorder>();
+        app.init_resource::<SaveStorage>();
+        app.add_system_to_stage(
+            PhysicsStage,
+            step_replay
+                .with_run_criteria(while_playing)
+                .label(ReplaySystem)
+                .after(StorePreviousTranslationSystem)
+                .before(FlapSystem),
+        );
+        app.add_system_set(SystemSet::on_enter(GameState::Playing).with_system(reset_replay));
+        app.add_system_set(
+            SystemSet::on_enter(GameState::GameOver)
+                .with_system(save_replay.after(UpdateBestScoreSystem)),
+        );
+    }
+}
+
+/// Play back a replay, on the course and preset it was recorded with
+pub struct ReplayPlaybackPlugin(pub Replay);
+
+impl Plugin for ReplayPlaybackPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SeedMode::Fixed(self.0.seed));
+        app.insert_resource(self.0.preset);
+        app.insert_resource(ReplayPlayer::new(self.0.clone()));
+    }
+}
+
+//
+// -- SYSTEM
+//
+
+fn reset_replay(
+    mut run_step: ResMut<RunStep>,
+    mut recorder: ResMut<ReplayRecorder>,
+    player: Option<ResMut<ReplayPlayer>>,
+) {
+    run_step.0 = 0;
+    recorder.flap_steps.clear();
+
+    if let Some(mut player) = player {
+        player.next_flap_index = 0;
+    }
+}
+
+fn step_replay(
+    mut run_step: ResMut<RunStep>,
+    mut flap_request: ResMut<FlapRequest>,
+    mut recorder: ResMut<ReplayRecorder>,
+    player: Option<ResMut<ReplayPlayer>>,
+    mut flappy_query: Query<
+        (&mut Transform, &mut PreviousTranslation, &mut Velocity),
+        With<Flappy>,
+    >,
+) {
+    let (mut flappy_transform, mut flappy_previous_translation, mut flappy_velocity) =
+        flappy_query.single_mut();
+
+    if let Some(mut player) = player {
+        if run_step.0 == 0 {
+            flappy_transform.translation.y = player.replay.start_y;
+            flappy_previous_translation.y = player.replay.start_y;
+            flappy_velocity.y = player.replay.start_velocity_y;
+        }
+
+        let next_flap_step = player.replay.flap_steps.get(player.next_flap_index);
+        flap_request.0 = next_flap_step == Some(&run_step.0);
+        if flap_request.0 {
+            player.next_flap_index += 1;
+        }
+    }
+
+    if run_step.0 == 0 {
+        recorder.start_y = flappy_transform.translation.y;
+        recorder.start_velocity_y = flappy_velocity.y;
+    }
+    if flap_request.0 {
+        recorder.flap_steps.push(run_step.0);
+    }
+    run_step.0 += 1;
+}
+
+fn save_replay(
+    storage: Res<SaveStorage>,
+    scoreboard: Res<Scoreboard>,
+    course_rng: Res<CourseRng>,
+    mut recorder: ResMut<ReplayRecorder>,
+    player: Option<Res<ReplayPlayer>>,
+) {
+    let replay = Replay {
+        seed: course_rng.seed(),
+        preset: scoreboard.preset(),
+        start_y: recorder.start_y,
+        start_velocity_y: recorder.start_velocity_y,
+        score: scoreboard.current_score(),
+        flap_steps: recorder.flap_steps.clone(),
+    };
+
+    // a playback is already saved
+    if player.is_none() {
+        let bytes = replay.to_bytes();
+        storage.save_bytes(LAST_REPLAY_FILE_NAME, &bytes);
+        if scoreboard.is_new_best() {
+            storage.save_bytes(&best_replay_file_name(replay.preset), &bytes);
+        }
+    }
+
+    recorder.last_replay = Some(replay);
+}
+
+/// Best run of `preset`, if one was saved and is still readable
+pub fn load_best_replay(storage: &SaveStorage, preset: Preset) -> Option<Replay> {
+    let file_name = best_replay_file_name(preset);
+    let bytes = storage.load_bytes(&file_name)?;
+
+    Replay::from_bytes(&bytes)
+        .map_err(|err| warn!("Ignoring {}: {}", file_name, err))
+        .ok()
+}
+
+fn best_replay_file_name(preset: Preset) -> String {
+    format!("best-{}.replay", preset.to_string().to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay() -> Replay {
+        Replay {
+            seed: 0x0123_4567_89ab_cdef,
+            preset: Preset::Hard,
+            start_y: 12.5,
+            start_velocity_y: -3.25,
+            score: 26,
+            flap_steps: vec![0, 1, 127, 128, 16_384, u32::MAX - 1, u32::MAX],
+        }
+    }
+
+    fn invalid_reason(bytes: &[u8]) -> &'static str {
+        match Replay::from_bytes(bytes) {
+            Err(ReplayError::Invalid(reason)) => reason,
+            other => panic!("expected an invalid replay, got {:?}", other),
+        }
+    }
+
+    fn varint(bytes: &[u8]) -> Result<u32, ReplayError> {
+        ReplayReader { bytes }.varint()
+    }
+
+    #[test]
+    fn replays_round_trip() {
+        let replay = replay();
+        assert_eq!(Replay::from_bytes(&replay.to_bytes()).unwrap(), replay);
+
+        let without_flaps = Replay {
+            score: u32::MAX,
+            flap_steps: Vec::new(),
+            ..replay
+        };
+        assert_eq!(
+            Replay::from_bytes(&without_flaps.to_bytes()).unwrap(),
+            without_flaps
+        );
+    }
+
+    #[test]
+    fn broken_replays_are_invalid() {
+        let bytes = replay().to_bytes();
+
+        for length in 0..bytes.len() {
+            assert_eq!(invalid_reason(&bytes[..length]), "truncated file");
+        }
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(invalid_reason(&bad_magic), "unknown file type");
+
+        for version in [REPLAY_VERSION - 1, REPLAY_VERSION + 1] {
+            let mut unknown_version = bytes.clone();
+            unknown_version[REPLAY_MAGIC.len()] = version;
+            assert_eq!(invalid_reason(&unknown_version), "unsupported version");
+        }
+
+        let mut unknown_preset = bytes.clone();
+        unknown_preset[REPLAY_MAGIC.len() + 1 + 8] = Preset::ALL.len() as u8;
+        assert_eq!(invalid_reason(&unknown_preset), "unknown preset");
+
+        let mut trailing_bytes = bytes;
+        trailing_bytes.push(0);
+        assert_eq!(
+            invalid_reason(&trailing_bytes),
+            "unexpected data after the flaps"
+        );
+    }
+
+    #[test]
+    fn varints_fit_in_u32() {
+        assert_eq!(varint(&[0x00]).unwrap(), 0);
+        assert_eq!(varint(&[0x80, 0x01]).unwrap(), 128);
+        assert_eq!(varint(&[0xff, 0xff, 0xff, 0xff, 0x0f]).unwrap(), u32::MAX);
+
+        for over_long in [
+            &[0xff, 0xff, 0xff, 0xff, 0x1f][..],
+            &[0x80, 0x80, 0x80, 0x80, 0x80, 0x00][..],
+        ] {
+            assert!(matches!(
+                varint(over_long),
+                Err(ReplayError::Invalid("number out of range"))
+            ));
+        }
+    }
+}
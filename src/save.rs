@@ -0,0 +1,221 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+const SAVE_DIRECTORY_NAME: &str = "bevy_flappy";
+
+/// Where the game data is persisted. Disabled storage never touches the disk.
+pub struct SaveStorage {
+    directory: Option<PathBuf>,
+}
+
+impl Default for SaveStorage {
+    /// The user's data directory, e.g. `~/.local/share/bevy_flappy` on Linux
+    fn default() -> Self {
+        SaveStorage {
+            directory: dirs::data_dir().map(|directory| directory.join(SAVE_DIRECTORY_NAME)),
+        }
+    }
+}
+
+impl SaveStorage {
+    pub fn disabled() -> Self {
+        SaveStorage { directory: None }
+    }
+
+    /// Read `file_name`, falling back to the default value when the file is
+    /// missing or corrupt. A corrupt file is kept aside so it is not lost on
+    /// the next save.
+    pub fn load<T: DeserializeOwned + Default>(&self, file_name: &str) -> T {
+        let Some(path) = self.path(file_name) else {
+            return T::default();
+        };
+
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return T::default(),
+            Err(err) => {
+                error!("Could not read {}: {}", path.display(), err);
+                return T::default();
+            }
+        };
+
+        ron::from_str(&content).unwrap_or_else(|err| {
+            let corrupt_path = path.with_extension("corrupt");
+            warn!(
+                "{} is corrupt ({}), moving it to {}",
+                path.display(),
+                err,
+                corrupt_path.display()
+            );
+            if let Err(err) = fs::rename(&path, &corrupt_path) {
+                error!("Could not move {}: {}", path.display(), err);
+            }
+
+            T::default()
+        })
+    }
+
+    /// Read `file_name` in any format, `None` when it is missing or unreadable
+    pub fn load_bytes(&self, file_name: &str) -> Option<Vec<u8>> {
+        let path = self.path(file_name)?;
+
+        match fs::read(&path) {
+            Ok(content) => Some(content),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => {
+                error!("Could not read {}: {}", path.display(), err);
+                None
+            }
+        }
+    }
+
+    /// Write `file_name` atomically: the content goes to a temporary file
+    /// first, then replaces the previous file in one rename.
+    pub fn save<T: Serialize>(&self, file_name: &str, value: &T) {
+        match ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default()) {
+            Ok(content) => self.save_bytes(file_name, content.as_bytes()),
+            Err(err) => error!("Could not serialize {}: {}", file_name, err),
+        }
+    }
+
+    /// Write `file_name` atomically, like `save`, with content in any format
+    pub fn save_bytes(&self, file_name: &str, content: &[u8]) {
+        let Some(path) = self.path(file_name) else {
+            return;
+        };
+
+        if let Err(err) = write_atomically(&path, content) {
+            error!("Could not save {}: {}", path.display(), err);
+        }
+    }
+
+    fn path(&self, file_name: &str) -> Option<PathBuf> {
+        self.directory
+            .as_ref()
+            .map(|directory| directory.join(file_name))
+    }
+}
+
+pub fn write_atomically(path: &Path, content: &[u8]) -> io::Result<()> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+
+    let temporary_path = path.with_extension("tmp");
+    let mut temporary_file = fs::File::create(&temporary_path)?;
+    temporary_file.write_all(content)?;
+    temporary_file.sync_all()?;
+
+    fs::rename(&temporary_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct Progress {
+        level: u32,
+        name: String,
+    }
+
+    /// Storage in a new directory of the system temporary one, removed when
+    /// dropped
+    struct TemporaryStorage(SaveStorage);
+
+    impl TemporaryStorage {
+        fn new(test_name: &str) -> Self {
+            let directory = std::env::temp_dir().join(format!(
+                "{}-{}-{}",
+                SAVE_DIRECTORY_NAME,
+                test_name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&directory);
+
+            TemporaryStorage(SaveStorage {
+                directory: Some(directory),
+            })
+        }
+
+        fn path(&self, file_name: &str) -> PathBuf {
+            self.0.path(file_name).unwrap()
+        }
+    }
+
+    impl Drop for TemporaryStorage {
+        fn drop(&mut self) {
+            if let Some(directory) = &self.0.directory {
+                let _ = fs::remove_dir_all(directory);
+            }
+        }
+    }
+
+    #[test]
+    fn saved_values_load_back() {
+        let storage = TemporaryStorage::new("saved");
+        let progress = Progress {
+            level: 3,
+            name: "flappy".to_string(),
+        };
+
+        // the directory is created on the first save
+        storage.0.save("progress.ron", &progress);
+        assert_eq!(storage.0.load::<Progress>("progress.ron"), progress);
+        assert!(!storage.path("progress.tmp").exists());
+
+        let progress = Progress {
+            level: 4,
+            ..progress
+        };
+        storage.0.save("progress.ron", &progress);
+        assert_eq!(storage.0.load::<Progress>("progress.ron"), progress);
+    }
+
+    #[test]
+    fn missing_files_load_as_default() {
+        let storage = TemporaryStorage::new("missing");
+        assert_eq!(
+            storage.0.load::<Progress>("progress.ron"),
+            Progress::default()
+        );
+        assert_eq!(storage.0.load_bytes("progress.ron"), None);
+
+        let disabled = SaveStorage::disabled();
+        disabled.save("progress.ron", &Progress::default());
+        assert_eq!(
+            disabled.load::<Progress>("progress.ron"),
+            Progress::default()
+        );
+    }
+
+    #[test]
+    fn corrupt_files_are_moved_aside() {
+        let storage = TemporaryStorage::new("corrupt");
+        storage.0.save_bytes("progress.ron", b"(level: \"three\")");
+
+        assert_eq!(
+            storage.0.load::<Progress>("progress.ron"),
+            Progress::default()
+        );
+        assert!(!storage.path("progress.ron").exists());
+        assert_eq!(
+            fs::read(storage.path("progress.corrupt")).unwrap(),
+            b"(level: \"three\")"
+        );
+
+        // and not overwritten by the next save
+        storage.0.save("progress.ron", &Progress::default());
+        assert_eq!(
+            storage.0.load::<Progress>("progress.ron"),
+            Progress::default()
+        );
+        assert!(storage.path("progress.corrupt").exists());
+    }
+}
@@ -1,53 +1,252 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::collider::CollisionLayers;
+use crate::collision::{CollisionStarted, CollisionSystem};
+use crate::difficulty::Preset;
+use crate::game_state::{while_playing, GameState};
+use crate::physics::PhysicsStage;
+use crate::save::SaveStorage;
+use crate::seed::CourseRng;
 
-use crate::game_state::GameState;
+const SCORES_FILE_NAME: &str = "scores.ron";
+// How many of the best runs are kept
+const SCORE_HISTORY_LENGTH: usize = 10;
 
 #[derive(Debug, Clone)]
 pub struct Scoreboard {
     current_score: u32,
-    best_score: u32,
+    /// Preset of the current run, or of the last one when not playing
+    preset: Preset,
+    best_scores: HashMap<Preset, u32>,
+    /// Whether the last finished run beat the previous best score of its preset
+    new_best: bool,
+    /// Best runs, highest score first
+    history: Vec<ScoreRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoreRecord {
+    pub score: u32,
+    pub seed: u64,
+    pub preset: Preset,
+    /// Seconds since the unix epoch
+    pub timestamp: u64,
+}
+
+/// Persisted part of the `Scoreboard`
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ScoresSave {
+    best_scores: HashMap<Preset, u32>,
+    history: Vec<ScoreRecord>,
 }
 
 impl Scoreboard {
     fn new() -> Self {
         Self {
             current_score: 0,
-            best_score: 0,
+            preset: Preset::default(),
+            best_scores: HashMap::new(),
+            new_best: false,
+            history: Vec::new(),
         }
     }
 
+    pub fn current_score(&self) -> u32 {
+        self.current_score
+    }
+
+    pub fn preset(&self) -> Preset {
+        self.preset
+    }
+
+    /// Best score of the preset of the current run
+    pub fn best_score(&self) -> u32 {
+        self.best_scores.get(&self.preset).copied().unwrap_or(0)
+    }
+
+    pub fn is_new_best(&self) -> bool {
+        self.new_best
+    }
+
     pub fn update_current_score(&mut self, increment: u32) {
         self.current_score += increment;
-        println!("{}", self.current_score);
     }
 
     fn update_best_score(&mut self) {
-        if self.current_score > self.best_score {
-            self.best_score = self.current_score
+        self.new_best = self.current_score > self.best_score();
+        if self.new_best {
+            self.best_scores.insert(self.preset, self.current_score);
         }
     }
 
-    fn reset_current_score(&mut self) {
+    fn record_current_score(&mut self, seed: u64) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_secs());
+
+        // after runs with the same score, so older runs keep their rank
+        let index = self
+            .history
+            .partition_point(|record| record.score >= self.current_score);
+        self.history.insert(
+            index,
+            ScoreRecord {
+                score: self.current_score,
+                seed,
+                preset: self.preset,
+                timestamp,
+            },
+        );
+        self.history.truncate(SCORE_HISTORY_LENGTH);
+    }
+
+    fn reset_current_score(&mut self, preset: Preset) {
         self.current_score = 0;
+        self.preset = preset;
+        self.new_best = false;
     }
 }
 
 pub struct ScorePlugin;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+pub struct UpdateBestScoreSystem;
+
 impl Plugin for ScorePlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(Scoreboard::new());
-        app.add_system_set(SystemSet::on_enter(GameState::GameOver).with_system(update_best_score));
+        app.init_resource::<SaveStorage>();
+        app.add_startup_system(load_scores);
+        app.add_system_set(
+            SystemSet::on_enter(GameState::GameOver)
+                .with_system(update_best_score.label(UpdateBestScoreSystem))
+                .with_system(save_scores.after(UpdateBestScoreSystem)),
+        );
         app.add_system_set(
-            SystemSet::on_exit(GameState::GameOver).with_system(reset_current_score),
+            SystemSet::on_enter(GameState::Playing).with_system(reset_current_score),
         );
+        app.add_system_to_stage(
+            PhysicsStage,
+            score_triggers
+                .with_run_criteria(while_playing)
+                .after(CollisionSystem),
+        );
+    }
+}
+
+/// A trigger scores once, it is gone after being touched
+fn score_triggers(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionStarted>,
+    mut scoreboard: ResMut<Scoreboard>,
+) {
+    // despawned once the commands are applied, not before the other events
+    let mut scored_triggers = Vec::new();
+    for event in collision_events.iter() {
+        if let Some((_, trigger_entity)) =
+            event.between(CollisionLayers::PLAYER, CollisionLayers::SCORE_TRIGGER)
+        {
+            if scored_triggers.contains(&trigger_entity) {
+                continue;
+            }
+            scored_triggers.push(trigger_entity);
+            scoreboard.update_current_score(1);
+            commands.entity(trigger_entity).despawn();
+        }
     }
 }
 
-fn reset_current_score(mut scoreboard: ResMut<Scoreboard>) {
-    scoreboard.reset_current_score();
+fn load_scores(storage: Res<SaveStorage>, mut scoreboard: ResMut<Scoreboard>) {
+    let mut scores: ScoresSave = storage.load(SCORES_FILE_NAME);
+    scores
+        .history
+        .sort_by(|a, b| b.score.cmp(&a.score).then(a.timestamp.cmp(&b.timestamp)));
+    scores.history.truncate(SCORE_HISTORY_LENGTH);
+
+    scoreboard.best_scores = scores.best_scores;
+    scoreboard.history = scores.history;
+}
+
+fn reset_current_score(mut scoreboard: ResMut<Scoreboard>, preset: Res<Preset>) {
+    scoreboard.reset_current_score(*preset);
 }
 
-fn update_best_score(mut scoreboard: ResMut<Scoreboard>) {
+fn update_best_score(mut scoreboard: ResMut<Scoreboard>, course_rng: Res<CourseRng>) {
     scoreboard.update_best_score();
+    scoreboard.record_current_score(course_rng.seed());
+}
+
+fn save_scores(storage: Res<SaveStorage>, scoreboard: Res<Scoreboard>) {
+    storage.save(
+        SCORES_FILE_NAME,
+        &ScoresSave {
+            best_scores: scoreboard.best_scores.clone(),
+            history: scoreboard.history.clone(),
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world_with_triggers() -> (World, Entity, Entity) {
+        let mut world = World::new();
+        world.insert_resource(Scoreboard::new());
+        world.init_resource::<Events<CollisionStarted>>();
+        let player = world.spawn().id();
+        let trigger = world.spawn().id();
+
+        (world, player, trigger)
+    }
+
+    fn run_score_triggers(world: &mut World, events: &[CollisionStarted]) {
+        world
+            .resource_mut::<Events<CollisionStarted>>()
+            .extend(events.iter().copied());
+        SystemStage::single_threaded()
+            .with_system(score_triggers)
+            .run(world);
+    }
+
+    #[test]
+    fn trigger_scores_once() {
+        let (mut world, player, trigger) = world_with_triggers();
+
+        // in either order, in the same step
+        run_score_triggers(
+            &mut world,
+            &[
+                CollisionStarted::overlapping(
+                    [trigger, player],
+                    [CollisionLayers::SCORE_TRIGGER, CollisionLayers::PLAYER],
+                ),
+                CollisionStarted::overlapping(
+                    [player, trigger],
+                    [CollisionLayers::PLAYER, CollisionLayers::SCORE_TRIGGER],
+                ),
+            ],
+        );
+        assert_eq!(world.resource::<Scoreboard>().current_score(), 1);
+        assert!(world.get_entity(trigger).is_none());
+    }
+
+    #[test]
+    fn only_triggers_score() {
+        let (mut world, player, pipe) = world_with_triggers();
+
+        run_score_triggers(
+            &mut world,
+            &[CollisionStarted::overlapping(
+                [player, pipe],
+                [CollisionLayers::PLAYER, CollisionLayers::OBSTACLE],
+            )],
+        );
+        assert_eq!(world.resource::<Scoreboard>().current_score(), 0);
+        assert!(world.get_entity(pipe).is_some());
+    }
 }
@@ -0,0 +1,73 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::cli;
+
+const SECONDS_PER_DAY: u64 = 60 * 60 * 24;
+
+/// Where the seed of each run comes from
+#[derive(Debug, Clone, Copy, Default)]
+pub enum SeedMode {
+    /// A new course every run
+    #[default]
+    Random,
+    /// The same course for everyone during a UTC day
+    Daily,
+    /// The same course every run
+    Fixed(u64),
+}
+
+impl SeedMode {
+    /// `--seed N` replays a given course, `--daily` plays the course of the day
+    pub fn from_args() -> Self {
+        if let Some(seed) = cli::arg_value("--seed") {
+            SeedMode::Fixed(seed.parse().expect("--seed must be a positive integer"))
+        } else if cli::has_flag("--daily") {
+            SeedMode::Daily
+        } else {
+            SeedMode::Random
+        }
+    }
+
+    pub fn next_seed(&self) -> u64 {
+        match self {
+            SeedMode::Random => rand::thread_rng().gen(),
+            SeedMode::Daily => daily_seed(),
+            SeedMode::Fixed(seed) => *seed,
+        }
+    }
+}
+
+fn daily_seed() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock is set before 1970");
+
+    now.as_secs() / SECONDS_PER_DAY
+}
+
+/// Random generator driving the course layout. Reseeded at the start of each
+/// run so a seed always produces the same course.
+pub struct CourseRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl CourseRng {
+    pub fn new(seed: u64) -> Self {
+        CourseRng {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+}
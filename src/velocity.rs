@@ -1,5 +1,8 @@
 use bevy::prelude::*;
 
+use crate::game_state::unless_paused;
+use crate::physics::{PhysicsStage, StorePreviousTranslationSystem, PHYSICS_DELTA_SECONDS};
+
 pub struct VelocityPlugin;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
@@ -7,16 +10,22 @@ pub struct ApplyVelocitySystem;
 
 impl Plugin for VelocityPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(apply_velocity.label(ApplyVelocitySystem));
+        app.add_system_to_stage(
+            PhysicsStage,
+            apply_velocity
+                .with_run_criteria(unless_paused)
+                .label(ApplyVelocitySystem)
+                .after(StorePreviousTranslationSystem),
+        );
     }
 }
 
 #[derive(Component, Deref, DerefMut, Debug)]
 pub struct Velocity(pub Vec2);
 
-fn apply_velocity(time: Res<Time>, mut query: Query<(&mut Transform, &Velocity)>) {
+fn apply_velocity(mut query: Query<(&mut Transform, &Velocity)>) {
     for (mut transform, velocity) in query.iter_mut() {
-        transform.translation.x = transform.translation.x + velocity.x * time.delta_seconds();
-        transform.translation.y = transform.translation.y + velocity.y * time.delta_seconds();
+        transform.translation.x += velocity.x * PHYSICS_DELTA_SECONDS;
+        transform.translation.y += velocity.y * PHYSICS_DELTA_SECONDS;
     }
 }
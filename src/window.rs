@@ -1,7 +1,5 @@
 use bevy::prelude::Color;
 
-pub const WINDOW_WIDTH: f32 = 500.0;
-pub const WINDOW_HEIGHT: f32 = 700.0;
 /** Limit of how far objects can go over the window size */
 pub const WINDOW_BOUND_LIMIT: f32 = 400.0;
 